@@ -1,41 +1,46 @@
 use anyhow::anyhow;
-use std::collections::HashMap;
-use std::error::Error;
 use std::fmt::Debug;
 use std::future::Future;
-use std::path::Path;
 use std::pin::Pin;
-use std::time::Duration;
+use std::sync::Arc;
 
+use google_youtube3::hyper;
 use google_youtube3::hyper::client::HttpConnector;
 use google_youtube3::hyper_rustls::HttpsConnector;
 use google_youtube3::oauth2;
 use google_youtube3::oauth2::authenticator::Authenticator;
 use google_youtube3::oauth2::authenticator_delegate::InstalledFlowDelegate;
-use strfmt::strfmt;
-use tokio::time::sleep;
+use google_youtube3::oauth2::authorized_user::AuthorizedUserSecret;
 
+use crate::auth_code::AuthCodeProvider;
 use crate::prelude::*;
-use downloader_config::{load_config, Config};
+use crate::token_store::{TokenStore, UserTokenStorage};
+#[derive(Debug)]
 struct CustomFlowDelegate {
     user: String,
+    redirect_uri: String,
+    code_provider: Arc<dyn AuthCodeProvider>,
 }
 
 impl CustomFlowDelegate {
-    fn new(user: String) -> Self {
-        Self { user }
+    fn new(user: String, redirect_uri: String, code_provider: Arc<dyn AuthCodeProvider>) -> Self {
+        Self {
+            user,
+            redirect_uri,
+            code_provider,
+        }
     }
 }
 
 impl InstalledFlowDelegate for CustomFlowDelegate {
     #[cfg_attr(feature = "tracing", tracing::instrument)]
     fn redirect_uri(&self) -> Option<&str> {
-        if load_config().use_local_auth_redirect {
-            trace!("local redirect uri");
-            Some("http://localhost:8080/googleapi/auth")
+        if let Some(uri) = self.code_provider.redirect_uri() {
+            trace!("redirect uri of the code provider");
+            Some(uri)
         } else {
-            trace!("remote redirect uri");
-            Some("https://game-omgeeky.de:7443/googleapi/auth")
+            trace!("configured redirect uri");
+            Some(&self.redirect_uri)
         }
     }
 
@@ -56,17 +61,10 @@ impl CustomFlowDelegate {
         );
         info!("Please open this URL in your browser:\n{}\n", url);
         if need_code {
-            let conf = load_config();
-
-            let mut code = String::new();
-            if conf.use_file_auth_response {
-                code = get_auth_code(&conf).await.unwrap_or("".to_string());
-            } else {
-                println!("Enter the code you get after authorization here: ");
-                info!("Enter the code you get after authorization here: ");
-                std::io::stdin().read_line(&mut code).unwrap();
-            }
-            Ok(code.trim().to_string())
+            self.code_provider
+                .get_code(&self.user, url)
+                .await
+                .map_err(|e| format!("could not get the auth code: {}", e))
         } else {
             println!("No code needed");
             info!("No code needed");
@@ -74,103 +72,141 @@ impl CustomFlowDelegate {
         }
     }
 }
-#[cfg_attr(feature = "tracing", tracing::instrument)]
-async fn get_auth_code(config: &Config) -> Result<String, Box<dyn Error>> {
-    let code: String;
-
-    let path = &config.path_auth_code;
-    let path = Path::new(path);
-    if let Err(e) = std::fs::remove_file(path) {
-        if e.kind() != std::io::ErrorKind::NotFound {
-            println!("Error removing file: {:?}", e);
-            error!("Error removing file: {:?}", e);
-            panic!("Error removing file: {:?}", e);
-        }
-    }
 
-    println!("Waiting for auth code in file: {}", path.display());
-    info!("Waiting for auth code in file: {}", path.display());
-    loop {
-        let res = std::fs::read_to_string(path); //try reading the file
-        if let Ok(content) = res {
-            let l = content.lines().next(); //code should be on first line of the file
-            let re = match l {
-                Some(s) => s,
-                None => {
-                    sleep(Duration::from_secs(config.auth_file_read_timeout)).await;
-                    continue;
-                }
-            };
+/// How the client authenticates against the youtube api.
+#[derive(Clone, Default)]
+pub enum AuthMode {
+    /// The browser based installed flow with the application secret, the code
+    /// is delivered by the [`AuthCodeProvider`].
+    #[default]
+    InstalledFlow,
+    /// A service account, the application secret path points to its key.
+    ///
+    /// With a subject the service account acts as that user through domain-wide delegation.
+    ServiceAccount { subject: Option<String> },
+    /// An access token that was issued elsewhere. It is used as is and never refreshed.
+    AccessToken(String),
+    /// A refresh token that was issued for the client of the application secret.
+    RefreshToken(String),
+}
 
-            code = re.to_string();
-            // std::fs::remove_file(path)?;
-            break;
+impl Debug for AuthMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // the tokens are secrets and must not end up in logs
+        match self {
+            AuthMode::InstalledFlow => f.write_str("InstalledFlow"),
+            AuthMode::ServiceAccount { subject } => f
+                .debug_struct("ServiceAccount")
+                .field("subject", subject)
+                .finish(),
+            AuthMode::AccessToken(_) => f.write_str("AccessToken(..)"),
+            AuthMode::RefreshToken(_) => f.write_str("RefreshToken(..)"),
         }
-        // wait a few seconds
-        sleep(Duration::from_secs(config.auth_file_read_timeout)).await;
     }
+}
 
-    Ok(code)
+/// Everything besides the http client that is needed to build the authenticator.
+#[derive(Debug)]
+pub(crate) struct AuthSettings {
+    pub application_secret_path: Option<String>,
+    pub scopes: Vec<String>,
+    pub user: String,
+    pub mode: AuthMode,
+    pub code_provider: Arc<dyn AuthCodeProvider>,
+    pub token_store: Arc<dyn TokenStore>,
+    /// Used when the code provider does not need a specific redirect uri.
+    pub redirect_uri: String,
 }
 
 #[cfg_attr(feature = "tracing", tracing::instrument)]
 pub(crate) async fn get_authenticator(
-    path_to_application_secret: String,
-    scopes: &Vec<String>,
-    user: Option<impl Into<String> + Debug>,
+    settings: &AuthSettings,
+    hyper_client: hyper::Client<HttpsConnector<HttpConnector>>,
 ) -> Result<Authenticator<HttpsConnector<HttpConnector>>> {
-    let user = user.map(|x| x.into());
+    let AuthSettings {
+        application_secret_path: path_to_application_secret,
+        scopes,
+        user,
+        mode,
+        code_provider,
+        token_store,
+        redirect_uri,
+    } = settings;
     trace!(
-        "getting authenticator for user: {:?} with scopes: {:?} and secret_path: {}",
+        "getting authenticator for user: {:?} with scopes: {:?}, mode: {:?} and secret_path: {:?}",
         user,
         scopes,
+        mode,
         path_to_application_secret
     );
-    trace!("reading application secret");
-    let app_secret = oauth2::read_application_secret(path_to_application_secret).await?;
-    trace!("read application secret");
 
-    let config = load_config();
-    let mut vars: HashMap<String, String> = HashMap::new();
-    let user = match user {
-        Some(u) => u.into(),
-        None => "unknown".to_string(),
+    let storage = || Box::new(UserTokenStorage::new(token_store.clone(), user.clone()));
+    let secret_path = || {
+        path_to_application_secret
+            .clone()
+            .ok_or_else(|| anyhow!("an application secret path is required for {:?}", mode))
     };
-    vars.insert("user".to_string(), user.clone());
-    let persistent_path = strfmt(&config.path_authentications, &vars)
-        .map_err(|e| anyhow!("Error formatting path: {}", e))?;
-    let persistent_path: &Path = Path::new(&persistent_path);
-    debug!(
-        "Persistent auth path for user:{} => {}",
-        user,
-        persistent_path.display()
-    );
-    let persistent_path_parent = persistent_path
-        .parent()
-        .ok_or(anyhow!("could not get parent of path"))?;
-    if !persistent_path.exists() || persistent_path.is_dir() {
-        warn!(
-            "persistent path does not exist or is a dir: {}",
-            persistent_path.display()
-        );
-        let create_dir = std::fs::create_dir_all(persistent_path_parent);
-        warn!("result of create dir: {:?}", create_dir);
-    }
     trace!("building authenticator");
-    let method = oauth2::InstalledFlowReturnMethod::Interactive;
-    let auth = oauth2::InstalledFlowAuthenticator::builder(app_secret, method)
-        .flow_delegate(Box::new(CustomFlowDelegate::new(user)))
-        .persist_tokens_to_disk(persistent_path.to_path_buf())
-        .force_account_selection(true)
-        .build()
-        .await
-        //TODO: somehow get rid of this unwrap that is happening in the library
-        .map_err(|e| anyhow!("got an error from the authenticator: {}", e))?;
+    let auth = match mode {
+        AuthMode::InstalledFlow => {
+            let app_secret = oauth2::read_application_secret(secret_path()?).await?;
+            let method = oauth2::InstalledFlowReturnMethod::Interactive;
+            oauth2::InstalledFlowAuthenticator::builder(app_secret, method)
+                .hyper_client(hyper_client)
+                .flow_delegate(Box::new(CustomFlowDelegate::new(
+                    user.clone(),
+                    redirect_uri.clone(),
+                    code_provider.clone(),
+                )))
+                .with_storage(storage())
+                .force_account_selection(true)
+                .build()
+                .await
+        }
+        AuthMode::ServiceAccount { subject } => {
+            let key = oauth2::read_service_account_key(secret_path()?).await?;
+            let mut builder = oauth2::ServiceAccountAuthenticator::builder(key)
+                .hyper_client(hyper_client)
+                .with_storage(storage());
+            if let Some(subject) = subject {
+                debug!("impersonating {} with the service account", subject);
+                builder = builder.subject(subject.clone());
+            }
+            builder.build().await
+        }
+        AuthMode::AccessToken(token) => {
+            oauth2::AccessTokenAuthenticator::builder(token.clone())
+                .hyper_client(hyper_client)
+                .build()
+                .await
+        }
+        AuthMode::RefreshToken(refresh_token) => {
+            // the refresh token belongs to the client of the application secret
+            let app_secret = oauth2::read_application_secret(secret_path()?).await?;
+            let secret = AuthorizedUserSecret {
+                client_id: app_secret.client_id,
+                client_secret: app_secret.client_secret,
+                refresh_token: refresh_token.clone(),
+                key_type: "authorized_user".to_string(),
+            };
+            oauth2::AuthorizedUserAuthenticator::builder(secret)
+                .hyper_client(hyper_client)
+                .with_storage(storage())
+                .build()
+                .await
+        }
+    }
+    //TODO: somehow get rid of this unwrap that is happening in the library
+    .map_err(|e| anyhow!("got an error from the authenticator: {}", e))?;
     trace!("got authenticator, requesting scopes");
     let access_token = auth
-        .token(&scopes)
+        .token(scopes)
         .await
         .map_err(|e| anyhow!("could not get access to the requested scopes: {}", e))?;
-    trace!("got scope access: {:?}", access_token);
+    // never log the token itself
+    trace!(
+        "got scope access, the token expires at {:?}",
+        access_token.expiration_time()
+    );
     Ok(auth)
 }
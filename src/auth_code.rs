@@ -0,0 +1,196 @@
+use std::fmt::Debug;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use tokio::sync::{mpsc, oneshot, Mutex};
+use tokio::time::{sleep, Instant};
+
+use crate::prelude::*;
+use crate::redirect_server::LocalRedirectServer;
+
+pub type AuthCodeError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Delivers the code the user gets after authorizing the application.
+///
+/// The installed flow asks the provider for the code after the user was
+/// pointed to the authorization url.
+#[async_trait]
+pub trait AuthCodeProvider: Debug + Send + Sync {
+    /// The redirect uri the code is sent to, if the provider needs a specific one.
+    fn redirect_uri(&self) -> Option<&str> {
+        None
+    }
+
+    /// Waits for the code of the authorization of `user` at `url`.
+    async fn get_code(&self, user: &str, url: &str) -> Result<String, AuthCodeError>;
+}
+
+/// Asks for the code on the terminal.
+#[derive(Debug, Clone, Default)]
+pub struct StdinCodeProvider;
+
+#[async_trait]
+impl AuthCodeProvider for StdinCodeProvider {
+    async fn get_code(&self, _user: &str, _url: &str) -> Result<String, AuthCodeError> {
+        println!("Enter the code you get after authorization here: ");
+        info!("Enter the code you get after authorization here: ");
+        let code = tokio::task::spawn_blocking(|| {
+            let mut code = String::new();
+            std::io::stdin().read_line(&mut code).map(|_| code)
+        })
+        .await??;
+        Ok(code.trim().to_string())
+    }
+}
+
+/// Waits for the code to be written to the first line of a file.
+///
+/// A leftover file from an earlier authorization is removed before waiting.
+#[derive(Debug, Clone)]
+pub struct FileCodeProvider {
+    path: PathBuf,
+    poll_interval: Duration,
+    timeout: Option<Duration>,
+}
+
+impl FileCodeProvider {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            poll_interval: Duration::from_secs(5),
+            timeout: None,
+        }
+    }
+
+    /// How often the file is checked, defaults to 5 seconds.
+    pub fn poll_interval(mut self, interval: Duration) -> Self {
+        self.poll_interval = interval;
+        self
+    }
+
+    /// Give up if no code was written in this time.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    fn remove_file(&self) -> std::io::Result<()> {
+        match std::fs::remove_file(&self.path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[async_trait]
+impl AuthCodeProvider for FileCodeProvider {
+    async fn get_code(&self, _user: &str, _url: &str) -> Result<String, AuthCodeError> {
+        self.remove_file()?;
+        println!("Waiting for auth code in file: {}", self.path.display());
+        info!("Waiting for auth code in file: {}", self.path.display());
+        let deadline = self.timeout.map(|timeout| Instant::now() + timeout);
+        loop {
+            if let Ok(content) = tokio::fs::read_to_string(&self.path).await {
+                // the code is on the first line of the file
+                if let Some(code) = content.lines().next().filter(|l| !l.trim().is_empty()) {
+                    if let Err(e) = self.remove_file() {
+                        warn!("could not remove auth code file: {}", e);
+                    }
+                    return Ok(code.trim().to_string());
+                }
+            }
+            if deadline.map(|d| Instant::now() >= d).unwrap_or(false) {
+                return Err(format!(
+                    "no auth code was written to {} in time",
+                    self.path.display()
+                )
+                .into());
+            }
+            sleep(self.poll_interval).await;
+        }
+    }
+}
+
+#[async_trait]
+impl AuthCodeProvider for LocalRedirectServer {
+    fn redirect_uri(&self) -> Option<&str> {
+        Some(LocalRedirectServer::redirect_uri(self))
+    }
+
+    async fn get_code(&self, _user: &str, _url: &str) -> Result<String, AuthCodeError> {
+        Ok(self.wait_for_code().await?)
+    }
+}
+
+/// A request for an auth code sent by the [`ChannelCodeProvider`].
+#[derive(Debug)]
+pub struct AuthCodeRequest {
+    pub user: String,
+    /// The url the user has to open to authorize the application.
+    pub url: String,
+    code: oneshot::Sender<String>,
+}
+
+impl AuthCodeRequest {
+    /// Hands the code back to the waiting authenticator.
+    pub fn respond(self, code: impl Into<String>) {
+        if self.code.send(code.into()).is_err() {
+            warn!("the authenticator stopped waiting for the auth code");
+        }
+    }
+}
+
+/// Sends each authorization as an [`AuthCodeRequest`] to a channel, so the
+/// code can be collected anywhere, e.g. by a bot or a gui.
+#[derive(Debug)]
+pub struct ChannelCodeProvider {
+    requests: mpsc::Sender<AuthCodeRequest>,
+    redirect_uri: Option<String>,
+    // only one authorization should wait for a code at a time
+    lock: Mutex<()>,
+}
+
+impl ChannelCodeProvider {
+    /// Creates the provider and the receiver the requests arrive at.
+    pub fn new() -> (Self, mpsc::Receiver<AuthCodeRequest>) {
+        let (requests, receiver) = mpsc::channel(1);
+        let provider = Self {
+            requests,
+            redirect_uri: None,
+            lock: Mutex::new(()),
+        };
+        (provider, receiver)
+    }
+
+    /// The redirect uri the code is expected at, for example a page that shows the code.
+    pub fn with_redirect_uri(mut self, uri: impl Into<String>) -> Self {
+        self.redirect_uri = Some(uri.into());
+        self
+    }
+}
+
+#[async_trait]
+impl AuthCodeProvider for ChannelCodeProvider {
+    fn redirect_uri(&self) -> Option<&str> {
+        self.redirect_uri.as_deref()
+    }
+
+    async fn get_code(&self, user: &str, url: &str) -> Result<String, AuthCodeError> {
+        let _guard = self.lock.lock().await;
+        let (code, receiver) = oneshot::channel();
+        let request = AuthCodeRequest {
+            user: user.to_string(),
+            url: url.to_string(),
+            code,
+        };
+        self.requests
+            .send(request)
+            .await
+            .map_err(|_| "nobody is receiving auth code requests")?;
+        let code = receiver
+            .await
+            .map_err(|_| "the auth code request was dropped without a code")?;
+        Ok(code.trim().to_string())
+    }
+}
@@ -0,0 +1,417 @@
+use std::fmt::{Debug, Formatter};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use google_youtube3::{
+    hyper::{self, client::HttpConnector},
+    hyper_rustls::{HttpsConnector, HttpsConnectorBuilder},
+    oauth2::authenticator::Authenticator,
+    YouTube,
+};
+#[cfg(feature = "tracing")]
+use tracing::instrument;
+
+use crate::auth::{self, AuthMode, AuthSettings};
+use crate::auth_code::AuthCodeProvider;
+use crate::channel::ContentOwner;
+use crate::playlist_cache::PlaylistCache;
+use crate::prelude::*;
+use crate::quota::{QuotaPolicy, QuotaTracker, DEFAULT_DAILY_QUOTA};
+use crate::redirect_server::LocalRedirectServer;
+use crate::scopes::ScopeSet;
+use crate::token_store::{FileTokenStore, TokenStore};
+use crate::upload_session::UploadSessionStore;
+use crate::validation::ValidationMode;
+use crate::{YoutubeClient, YoutubeConfig, YoutubeError};
+
+/// Builder for a [`YoutubeClient`].
+///
+/// Every part of the client can be swapped out: the connector used for all
+/// requests, the authenticator, the urls the api is reached at and so on.
+/// Anything that is not set falls back to the same defaults [`YoutubeClient::new`] uses.
+#[derive(Default)]
+pub struct YoutubeClientBuilder {
+    application_secret_path: Option<String>,
+    scopes: Vec<String>,
+    user: Option<String>,
+    code_provider: Option<Arc<dyn AuthCodeProvider>>,
+    auth_mode: AuthMode,
+    token_store: Option<Arc<dyn TokenStore>>,
+    config: Option<YoutubeConfig>,
+    incremental_auth: Option<bool>,
+    hyper_client: Option<hyper::Client<HttpsConnector<HttpConnector>>>,
+    connector: Option<HttpsConnector<HttpConnector>>,
+    authenticator: Option<Authenticator<HttpsConnector<HttpConnector>>>,
+    authenticator_refreshable: bool,
+    base_url: Option<String>,
+    root_url: Option<String>,
+    user_agent: Option<String>,
+    connect_timeout: Option<Duration>,
+    pool_idle_timeout: Option<Duration>,
+    upload_session_path: Option<PathBuf>,
+    validation_mode: ValidationMode,
+    playlist_cache_ttl: Option<Duration>,
+    playlist_cache_path: Option<PathBuf>,
+    quota_budget: Option<u64>,
+    quota_policy: QuotaPolicy,
+    quota_usage_path: Option<PathBuf>,
+    content_owner: Option<ContentOwner>,
+}
+
+impl Debug for YoutubeClientBuilder {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("YoutubeClientBuilder")
+            .field("application_secret_path", &self.application_secret_path)
+            .field("scopes", &self.scopes)
+            .field("user", &self.user)
+            .field("code_provider", &self.code_provider)
+            .field("auth_mode", &self.auth_mode)
+            .field("token_store", &self.token_store)
+            .field("config", &self.config)
+            .field("incremental_auth", &self.incremental_auth)
+            .field("custom_hyper_client", &self.hyper_client.is_some())
+            .field("custom_connector", &self.connector.is_some())
+            .field("custom_authenticator", &self.authenticator.is_some())
+            .field("authenticator_refreshable", &self.authenticator_refreshable)
+            .field("base_url", &self.base_url)
+            .field("root_url", &self.root_url)
+            .field("user_agent", &self.user_agent)
+            .field("connect_timeout", &self.connect_timeout)
+            .field("pool_idle_timeout", &self.pool_idle_timeout)
+            .field("upload_session_path", &self.upload_session_path)
+            .field("validation_mode", &self.validation_mode)
+            .field("playlist_cache_ttl", &self.playlist_cache_ttl)
+            .field("playlist_cache_path", &self.playlist_cache_path)
+            .field("quota_budget", &self.quota_budget)
+            .field("quota_policy", &self.quota_policy)
+            .field("quota_usage_path", &self.quota_usage_path)
+            .field("content_owner", &self.content_owner)
+            .finish()
+    }
+}
+
+impl YoutubeClientBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Path to the application secret that is used to build the authenticator.
+    /// For [`AuthMode::ServiceAccount`] this is the path to the service account key.
+    ///
+    /// Required unless an [`Authenticator`] is provided with [`Self::authenticator`]
+    /// or an [`AuthMode::AccessToken`] is used.
+    pub fn application_secret_path(mut self, path: impl Into<String>) -> Self {
+        self.application_secret_path = Some(path.into());
+        self
+    }
+
+    pub fn scopes(mut self, scopes: Vec<impl Into<String>>) -> Self {
+        self.scopes = scopes.into_iter().map(|s| s.into()).collect();
+        self
+    }
+
+    pub fn add_scope(mut self, scope: impl Into<String>) -> Self {
+        self.scopes.push(scope.into());
+        self
+    }
+
+    /// Adds all scopes of the set.
+    pub fn scope_set(mut self, scopes: ScopeSet) -> Self {
+        self.scopes
+            .extend(scopes.urls().into_iter().map(String::from));
+        self
+    }
+
+    /// Request missing scopes when a method needs them instead of failing
+    /// with [`YoutubeError::MissingScope`]. Enabled by default.
+    pub fn incremental_authorization(mut self, enabled: bool) -> Self {
+        self.incremental_auth = Some(enabled);
+        self
+    }
+
+    /// Act as a content owner for one of its channels, for example a brand account
+    /// managed by the authenticated user.
+    ///
+    /// Every call that youtube allows it for is made on behalf of the content owner:
+    /// uploads, playlists, playlist items, video updates and thumbnails. Uploads and
+    /// new playlists go to the given channel. Listing video categories is the same
+    /// for everyone and ignores it.
+    pub fn on_behalf_of(mut self, owner: impl Into<String>, channel: impl Into<String>) -> Self {
+        self.content_owner = Some(ContentOwner {
+            owner: owner.into(),
+            channel: channel.into(),
+        });
+        self
+    }
+
+    /// The user the tokens are persisted for.
+    pub fn user(mut self, user: impl Into<String>) -> Self {
+        self.user = Some(user.into());
+        self
+    }
+
+    /// Receive the auth code through an embedded http listener instead of stdin or the code file.
+    ///
+    /// The redirect uri of the server has to be allowed for the application.
+    pub fn local_redirect(self, server: LocalRedirectServer) -> Self {
+        self.auth_code_provider(server)
+    }
+
+    /// Where the auth code of the installed flow comes from.
+    ///
+    /// Defaults to the [`AuthCodeSource`](crate::AuthCodeSource) of the config.
+    pub fn auth_code_provider(mut self, provider: impl AuthCodeProvider + 'static) -> Self {
+        self.code_provider = Some(Arc::new(provider));
+        self
+    }
+
+    /// Use an already built hyper client for all requests.
+    ///
+    /// Takes precedence over [`Self::connector`] and the timeout settings.
+    pub fn hyper_client(mut self, client: hyper::Client<HttpsConnector<HttpConnector>>) -> Self {
+        self.hyper_client = Some(client);
+        self
+    }
+
+    /// Use a custom connector instead of the default native-roots rustls connector.
+    ///
+    /// The connect timeout is not applied to a custom connector.
+    pub fn connector(mut self, connector: HttpsConnector<HttpConnector>) -> Self {
+        self.connector = Some(connector);
+        self
+    }
+
+    /// How the client authenticates, defaults to [`AuthMode::InstalledFlow`].
+    pub fn auth_mode(mut self, mode: AuthMode) -> Self {
+        self.auth_mode = mode;
+        self
+    }
+
+    /// The settings that are not covered by the other options, defaults to [`YoutubeConfig::default`].
+    pub fn config(mut self, config: YoutubeConfig) -> Self {
+        self.config = Some(config);
+        self
+    }
+
+    /// Where the tokens of the user are persisted.
+    ///
+    /// Defaults to a [`FileTokenStore`] at [`YoutubeConfig::path_authentications`].
+    pub fn token_store(mut self, store: impl TokenStore + 'static) -> Self {
+        self.token_store = Some(Arc::new(store));
+        self
+    }
+
+    /// Use a pre-built authenticator instead of running the installed flow.
+    ///
+    /// It is not known whether it can refresh its tokens, so
+    /// [`YoutubeClient::spawn_token_refresh`] does nothing unless
+    /// [`Self::authenticator_refreshable`] says so.
+    pub fn authenticator(mut self, auth: Authenticator<HttpsConnector<HttpConnector>>) -> Self {
+        self.authenticator = Some(auth);
+        self
+    }
+
+    /// Whether the authenticator given to [`Self::authenticator`] can get new
+    /// access tokens on its own, for example with a refresh token. Defaults to `false`.
+    pub fn authenticator_refreshable(mut self, refreshable: bool) -> Self {
+        self.authenticator_refreshable = refreshable;
+        self
+    }
+
+    /// Override the base url of the api (default: `https://youtube.googleapis.com/youtube/v3/`).
+    pub fn base_url(mut self, url: impl Into<String>) -> Self {
+        self.base_url = Some(url.into());
+        self
+    }
+
+    /// Override the root url used for uploads (default: `https://youtube.googleapis.com/`).
+    pub fn root_url(mut self, url: impl Into<String>) -> Self {
+        self.root_url = Some(url.into());
+        self
+    }
+
+    pub fn user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.user_agent = Some(user_agent.into());
+        self
+    }
+
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    pub fn pool_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.pool_idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Persist resumable upload sessions to this file, so uploads that were
+    /// interrupted by a restart continue where they left off.
+    pub fn upload_session_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.upload_session_path = Some(path.into());
+        self
+    }
+
+    /// How video metadata that exceeds the youtube limits is handled before uploading.
+    pub fn validation_mode(mut self, mode: ValidationMode) -> Self {
+        self.validation_mode = mode;
+        self
+    }
+
+    /// Cache the playlists of the user for the given time instead of listing
+    /// them on every lookup.
+    pub fn playlist_cache(mut self, ttl: Duration) -> Self {
+        self.playlist_cache_ttl = Some(ttl);
+        self
+    }
+
+    /// Also keep the playlist cache in this file. Only used together with [`Self::playlist_cache`].
+    pub fn playlist_cache_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.playlist_cache_path = Some(path.into());
+        self
+    }
+
+    /// The quota units the client may use per day, defaults to [`DEFAULT_DAILY_QUOTA`].
+    pub fn quota_budget(mut self, units: u64) -> Self {
+        self.quota_budget = Some(units);
+        self
+    }
+
+    /// What to do with calls that would exceed the quota budget.
+    pub fn quota_policy(mut self, policy: QuotaPolicy) -> Self {
+        self.quota_policy = policy;
+        self
+    }
+
+    /// Persist the quota usage of the day in this file.
+    pub fn quota_usage_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.quota_usage_path = Some(path.into());
+        self
+    }
+
+    /// Whether the authenticator is built here and keeps its tokens in the token store.
+    /// Provided authenticators and plain access tokens never touch the store.
+    pub(crate) fn uses_token_store(&self) -> bool {
+        self.authenticator.is_none() && !matches!(self.auth_mode, AuthMode::AccessToken(_))
+    }
+
+    pub(crate) fn take_config(&mut self) -> YoutubeConfig {
+        self.config.take().unwrap_or_default()
+    }
+
+    /// The token store that was set, a [`FileTokenStore`] at the path of the config otherwise.
+    pub(crate) fn take_token_store(&mut self, config: &YoutubeConfig) -> Arc<dyn TokenStore> {
+        self.token_store
+            .take()
+            .unwrap_or_else(|| Arc::new(FileTokenStore::new(config.path_authentications.clone())))
+    }
+
+    pub(crate) fn build_hyper_client(&mut self) -> hyper::Client<HttpsConnector<HttpConnector>> {
+        if let Some(client) = self.hyper_client.take() {
+            trace!("using provided hyper client");
+            return client;
+        }
+        let connector = match self.connector.take() {
+            Some(connector) => {
+                trace!("using provided connector");
+                connector
+            }
+            None => {
+                let mut http = HttpConnector::new();
+                http.enforce_http(false);
+                http.set_connect_timeout(self.connect_timeout);
+                HttpsConnectorBuilder::new()
+                    .with_native_roots()
+                    .https_or_http()
+                    .enable_http1()
+                    .enable_http2()
+                    .wrap_connector(http)
+            }
+        };
+        let mut builder = hyper::Client::builder();
+        if let Some(timeout) = self.pool_idle_timeout {
+            builder.pool_idle_timeout(timeout);
+        }
+        builder.build(connector)
+    }
+
+    #[cfg_attr(feature = "tracing", instrument)]
+    pub async fn build(mut self) -> Result<YoutubeClient, YoutubeError> {
+        let hyper_client = self.build_hyper_client();
+
+        let (scope_set, extra_scopes) = ScopeSet::parse(&self.scopes)?;
+        let config = self.take_config();
+        let uses_token_store = self.uses_token_store();
+        let token_store = self.take_token_store(&config);
+        let settings = AuthSettings {
+            application_secret_path: self.application_secret_path.take(),
+            scopes: std::mem::take(&mut self.scopes),
+            user: self.user.take().unwrap_or_else(|| "unknown".to_string()),
+            mode: std::mem::take(&mut self.auth_mode),
+            code_provider: self
+                .code_provider
+                .take()
+                .unwrap_or_else(|| config.code_provider()),
+            token_store,
+            redirect_uri: config.redirect_uri.clone(),
+        };
+        let refreshable = match self.authenticator {
+            Some(_) => self.authenticator_refreshable,
+            None => !matches!(settings.mode, AuthMode::AccessToken(_)),
+        };
+        let auth = match self.authenticator.take() {
+            Some(auth) => {
+                trace!("using provided authenticator");
+                auth
+            }
+            None => {
+                trace!(
+                    "getting authenticator from path: {:?}",
+                    settings.application_secret_path
+                );
+                auth::get_authenticator(&settings, hyper_client.clone())
+                    .await
+                    .map_err(YoutubeError::auth)?
+            }
+        };
+
+        trace!("creating youtube client");
+        let mut client: YouTube<HttpsConnector<HttpConnector>> =
+            YouTube::new(hyper_client, auth.clone());
+        if let Some(base_url) = self.base_url {
+            client.base_url(base_url);
+        }
+        if let Some(root_url) = self.root_url {
+            client.root_url(root_url);
+        }
+        if let Some(user_agent) = self.user_agent {
+            client.user_agent(user_agent);
+        }
+
+        Ok(YoutubeClient {
+            client,
+            upload_sessions: self.upload_session_path.map(UploadSessionStore::new),
+            validation_mode: self.validation_mode,
+            categories: Default::default(),
+            playlist_cache: self
+                .playlist_cache_ttl
+                .map(|ttl| PlaylistCache::new(ttl, self.playlist_cache_path)),
+            quota: QuotaTracker::new(
+                self.quota_budget.unwrap_or(DEFAULT_DAILY_QUOTA),
+                self.quota_policy,
+                self.quota_usage_path,
+            ),
+            config,
+            authenticator: auth,
+            scopes: std::sync::Mutex::new(scope_set),
+            extra_scopes,
+            incremental_auth: self.incremental_auth.unwrap_or(true),
+            token_events: tokio::sync::broadcast::channel(16).0,
+            user: settings.user,
+            token_store: uses_token_store.then_some(settings.token_store),
+            content_owner: self.content_owner,
+            refreshable,
+        })
+    }
+}
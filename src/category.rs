@@ -0,0 +1,239 @@
+use std::sync::OnceLock;
+
+use exponential_backoff::youtube::generic_check_backoff_youtube;
+use google_youtube3::{
+    api::VideoCategoryListResponse,
+    hyper::{client::HttpConnector, Body, Response},
+    hyper_rustls::HttpsConnector,
+    YouTube,
+};
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "tracing")]
+use tracing::instrument;
+
+use crate::quota::QuotaOperation;
+use crate::scopes::Scope;
+use crate::{YoutubeClient, YoutubeError};
+
+/// The category of a video.
+///
+/// The well known categories can be used directly, any other category can be
+/// given by its name (as youtube lists it for the region) or its id.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
+pub enum VideoCategory {
+    FilmAndAnimation,
+    AutosAndVehicles,
+    Music,
+    PetsAndAnimals,
+    Sports,
+    TravelAndEvents,
+    #[default]
+    Gaming,
+    PeopleAndBlogs,
+    Comedy,
+    Entertainment,
+    NewsAndPolitics,
+    HowtoAndStyle,
+    Education,
+    ScienceAndTechnology,
+    NonprofitsAndActivism,
+    /// A category by its title, matched case-insensitively.
+    Named(String),
+    /// A category by its id, used as is.
+    Id(String),
+}
+
+static KNOWN_CATEGORIES: [(VideoCategory, &str); 15] = [
+    (VideoCategory::FilmAndAnimation, "Film & Animation"),
+    (VideoCategory::AutosAndVehicles, "Autos & Vehicles"),
+    (VideoCategory::Music, "Music"),
+    (VideoCategory::PetsAndAnimals, "Pets & Animals"),
+    (VideoCategory::Sports, "Sports"),
+    (VideoCategory::TravelAndEvents, "Travel & Events"),
+    (VideoCategory::Gaming, "Gaming"),
+    (VideoCategory::PeopleAndBlogs, "People & Blogs"),
+    (VideoCategory::Comedy, "Comedy"),
+    (VideoCategory::Entertainment, "Entertainment"),
+    (VideoCategory::NewsAndPolitics, "News & Politics"),
+    (VideoCategory::HowtoAndStyle, "Howto & Style"),
+    (VideoCategory::Education, "Education"),
+    (VideoCategory::ScienceAndTechnology, "Science & Technology"),
+    (
+        VideoCategory::NonprofitsAndActivism,
+        "Nonprofits & Activism",
+    ),
+];
+
+impl VideoCategory {
+    /// Turns a name like "Entertainment" into the matching category.
+    pub fn from_name(name: &str) -> Self {
+        KNOWN_CATEGORIES
+            .iter()
+            .find(|(_, title)| title.eq_ignore_ascii_case(name))
+            .map(|(category, _)| category.clone())
+            .unwrap_or_else(|| VideoCategory::Named(name.to_string()))
+    }
+
+    /// The title youtube uses for this category, `None` for [`VideoCategory::Id`].
+    pub fn title(&self) -> Option<&str> {
+        match self {
+            VideoCategory::Named(name) => Some(name.as_str()),
+            VideoCategory::Id(_) => None,
+            known => KNOWN_CATEGORIES
+                .iter()
+                .find(|(category, _)| category == known)
+                .map(|(_, title)| *title),
+        }
+    }
+}
+
+/// Numbers are taken as ids, anything else as a name.
+impl From<String> for VideoCategory {
+    fn from(s: String) -> Self {
+        if !s.is_empty() && s.chars().all(|c| c.is_ascii_digit()) {
+            VideoCategory::Id(s)
+        } else {
+            VideoCategory::from_name(&s)
+        }
+    }
+}
+
+impl From<VideoCategory> for String {
+    fn from(category: VideoCategory) -> Self {
+        match category {
+            VideoCategory::Id(id) => id,
+            category => category.title().unwrap_or_default().to_string(),
+        }
+    }
+}
+
+/// A category as youtube lists it for a region.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CategoryInfo {
+    pub id: String,
+    pub title: String,
+    /// Whether videos can be uploaded into this category.
+    pub assignable: bool,
+}
+
+/// The categories available in a region.
+#[derive(Debug, Clone, Default)]
+pub struct CategoryRegistry {
+    pub categories: Vec<CategoryInfo>,
+}
+
+impl CategoryRegistry {
+    /// The categories shipped with the crate in `data/category_ids.json` (region US),
+    /// parsed on first use.
+    pub fn bundled() -> &'static Self {
+        static BUNDLED: OnceLock<CategoryRegistry> = OnceLock::new();
+        BUNDLED.get_or_init(|| {
+            let categories = serde_json::from_str(include_str!("../data/category_ids.json"))
+                .expect("the bundled category ids are valid json");
+            Self { categories }
+        })
+    }
+
+    pub fn get(&self, id: &str) -> Option<&CategoryInfo> {
+        self.categories.iter().find(|c| c.id == id)
+    }
+
+    /// Finds the category by title, assignable categories take precedence over
+    /// the ones that share the same title.
+    pub fn find_by_title(&self, title: &str) -> Option<&CategoryInfo> {
+        let mut matching = self
+            .categories
+            .iter()
+            .filter(|c| c.title.eq_ignore_ascii_case(title));
+        let first = matching.next()?;
+        if first.assignable {
+            return Some(first);
+        }
+        matching.find(|c| c.assignable).or(Some(first))
+    }
+
+    /// The id of the category in this registry.
+    pub fn resolve(&self, category: &VideoCategory) -> Option<String> {
+        match category {
+            VideoCategory::Id(id) => Some(id.clone()),
+            category => category
+                .title()
+                .and_then(|title| self.find_by_title(title))
+                .map(|c| c.id.clone()),
+        }
+    }
+}
+
+impl YoutubeClient {
+    /// The video categories youtube lists for the region, cached after the first call.
+    #[cfg_attr(feature = "tracing", instrument)]
+    pub async fn video_categories(
+        &self,
+        region_code: &str,
+    ) -> Result<CategoryRegistry, YoutubeError> {
+        let mut categories = self.categories.lock().await;
+        if let Some(registry) = categories.get(region_code) {
+            return Ok(registry.clone());
+        }
+
+        struct CategoryParams {
+            part: Vec<String>,
+            region_code: String,
+        }
+        async fn list_categories(
+            client: &YouTube<HttpsConnector<HttpConnector>>,
+            params: &CategoryParams,
+        ) -> google_youtube3::Result<(Response<Body>, VideoCategoryListResponse)> {
+            client
+                .video_categories()
+                .list(&params.part)
+                .region_code(&params.region_code)
+                .doit()
+                .await
+        }
+        let params = CategoryParams {
+            part: vec!["snippet".to_string()],
+            region_code: region_code.to_string(),
+        };
+        self.prepare_call(Scope::Readonly, QuotaOperation::List)
+            .await?;
+        let (_res, response) = YoutubeError::api(
+            generic_check_backoff_youtube(&self.client, &params, list_categories).await,
+        )
+        .await?;
+
+        let registry = CategoryRegistry {
+            categories: response
+                .items
+                .unwrap_or_default()
+                .into_iter()
+                .filter_map(|category| {
+                    let snippet = category.snippet?;
+                    Some(CategoryInfo {
+                        id: category.id?,
+                        title: snippet.title?,
+                        assignable: snippet.assignable.unwrap_or(false),
+                    })
+                })
+                .collect(),
+        };
+        categories.insert(region_code.to_string(), registry.clone());
+        Ok(registry)
+    }
+
+    /// Looks up the id of the category, in the categories of the region if one
+    /// is given or in the bundled categories otherwise.
+    #[cfg_attr(feature = "tracing", instrument)]
+    pub async fn resolve_category(
+        &self,
+        category: &VideoCategory,
+        region_code: Option<&str>,
+    ) -> Result<String, YoutubeError> {
+        let id = match region_code {
+            Some(region_code) => self.video_categories(region_code).await?.resolve(category),
+            None => CategoryRegistry::bundled().resolve(category),
+        };
+        id.ok_or_else(|| YoutubeError::UnknownCategory(format!("{:?}", category)))
+    }
+}
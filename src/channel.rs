@@ -0,0 +1,179 @@
+use exponential_backoff::youtube::generic_check_backoff_youtube;
+use google_youtube3::{
+    api::{Channel, ChannelListResponse},
+    hyper::{client::HttpConnector, Body, Response},
+    hyper_rustls::HttpsConnector,
+    YouTube,
+};
+#[cfg(feature = "tracing")]
+use tracing::instrument;
+
+use crate::prelude::*;
+use crate::quota::QuotaOperation;
+use crate::scopes::Scope;
+use crate::{YoutubeClient, YoutubeError};
+
+/// A content owner (youtube partner) acting for one of its channels.
+///
+/// Set with [`YoutubeClientBuilder::on_behalf_of`](crate::YoutubeClientBuilder::on_behalf_of),
+/// the channel is then used instead of the channel of the authenticated account.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContentOwner {
+    /// The content owner id the authenticated account belongs to.
+    pub owner: String,
+    /// The id of the channel the requests are made for.
+    pub channel: String,
+}
+
+/// The body of a request together with the content owner it is made for.
+pub(crate) struct OwnedRequest<T> {
+    pub(crate) body: T,
+    pub(crate) content_owner: Option<ContentOwner>,
+}
+
+/// Whether the channel can upload videos longer than 15 minutes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LongUploadsStatus {
+    Allowed,
+    /// Not allowed yet, but the channel can get it by verifying the account.
+    Eligible,
+    Disallowed,
+    Unspecified,
+    Other(String),
+}
+
+impl From<&str> for LongUploadsStatus {
+    fn from(status: &str) -> Self {
+        match status {
+            "allowed" => LongUploadsStatus::Allowed,
+            "eligible" => LongUploadsStatus::Eligible,
+            "disallowed" => LongUploadsStatus::Disallowed,
+            "longUploadsUnspecified" => LongUploadsStatus::Unspecified,
+            other => LongUploadsStatus::Other(other.to_string()),
+        }
+    }
+}
+
+/// The parts of a channel that matter before uploading to it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChannelInfo {
+    pub id: String,
+    pub title: String,
+    /// The playlist every uploaded video ends up in.
+    pub uploads_playlist_id: Option<String>,
+    /// `None` if the channel hides its subscriber count.
+    pub subscriber_count: Option<u64>,
+    pub long_uploads: LongUploadsStatus,
+    /// Whether the channel is linked to a google account.
+    pub is_linked: bool,
+}
+
+impl ChannelInfo {
+    /// Youtube only allows long uploads for verified accounts, that is the
+    /// only verification the api tells anything about.
+    pub fn is_verified(&self) -> bool {
+        self.long_uploads == LongUploadsStatus::Allowed
+    }
+}
+
+impl TryFrom<Channel> for ChannelInfo {
+    type Error = YoutubeError;
+
+    fn try_from(channel: Channel) -> Result<Self, Self::Error> {
+        let id = channel.id.ok_or(YoutubeError::MissingId("channel"))?;
+        let statistics = channel.statistics.unwrap_or_default();
+        let status = channel.status.unwrap_or_default();
+        Ok(ChannelInfo {
+            id,
+            title: channel
+                .snippet
+                .and_then(|snippet| snippet.title)
+                .unwrap_or_default(),
+            uploads_playlist_id: channel
+                .content_details
+                .and_then(|details| details.related_playlists)
+                .and_then(|playlists| playlists.uploads),
+            subscriber_count: match statistics.hidden_subscriber_count {
+                Some(true) => None,
+                _ => statistics.subscriber_count,
+            },
+            long_uploads: status
+                .long_uploads_status
+                .as_deref()
+                .map(LongUploadsStatus::from)
+                .unwrap_or(LongUploadsStatus::Unspecified),
+            is_linked: status.is_linked.unwrap_or(false),
+        })
+    }
+}
+
+impl YoutubeClient {
+    /// Wraps the body with the content owner set with [`YoutubeClientBuilder::on_behalf_of`](crate::YoutubeClientBuilder::on_behalf_of).
+    pub(crate) fn owned_request<T>(&self, body: T) -> OwnedRequest<T> {
+        OwnedRequest {
+            body,
+            content_owner: self.content_owner.clone(),
+        }
+    }
+
+    /// The channel uploads go to: the channel of the authenticated account or
+    /// the one set with [`YoutubeClientBuilder::on_behalf_of`](crate::YoutubeClientBuilder::on_behalf_of).
+    #[cfg_attr(feature = "tracing", instrument)]
+    pub async fn my_channel(&self) -> Result<ChannelInfo, YoutubeError> {
+        struct ChannelParams {
+            part: Vec<String>,
+            content_owner: Option<ContentOwner>,
+        }
+        async fn my_channel(
+            client: &YouTube<HttpsConnector<HttpConnector>>,
+            params: &ChannelParams,
+        ) -> google_youtube3::Result<(Response<Body>, ChannelListResponse)> {
+            let call = client.channels().list(&params.part);
+            match &params.content_owner {
+                Some(owner) => {
+                    call.on_behalf_of_content_owner(&owner.owner)
+                        .add_id(&owner.channel)
+                        .doit()
+                        .await
+                }
+                None => call.mine(true).doit().await,
+            }
+        }
+        let params = ChannelParams {
+            part: ["snippet", "contentDetails", "statistics", "status"]
+                .iter()
+                .map(|p| p.to_string())
+                .collect(),
+            content_owner: self.content_owner.clone(),
+        };
+        self.prepare_call(Scope::Readonly, QuotaOperation::List)
+            .await?;
+        let (_res, channels) = YoutubeError::api(
+            generic_check_backoff_youtube(&self.client, &params, my_channel).await,
+        )
+        .await?;
+        channels
+            .items
+            .and_then(|items| items.into_iter().next())
+            .ok_or(YoutubeError::ChannelNotFound)?
+            .try_into()
+    }
+
+    /// Makes sure the client is authenticated as the channel with the given id,
+    /// so nothing gets uploaded to the wrong channel.
+    #[cfg_attr(feature = "tracing", instrument)]
+    pub async fn verify_channel(&self, channel_id: &str) -> Result<ChannelInfo, YoutubeError> {
+        let channel = self.my_channel().await?;
+        if channel.id != channel_id {
+            return Err(YoutubeError::WrongChannel {
+                expected: channel_id.to_string(),
+                actual: channel.id,
+            });
+        }
+        debug!(
+            "authenticated as channel {} ({})",
+            channel.title, channel.id
+        );
+        Ok(channel)
+    }
+}
@@ -1,32 +1,185 @@
-// use std::env;
-//
-// #[derive(Clone)]
-// pub struct Config {
-//     pub path_auth_code: String,
-//     pub path_authentications: String,
-//     pub use_file_auth_response: bool,
-//     pub use_local_auth_redirect: bool,
-//     pub auth_file_read_timeout: u64,
-// }
-//
-// pub fn load_config() -> Config {
-//     let path_auth_code =
-//         env::var("PATH_AUTH_CODE").unwrap_or("/tmp/twba/auth/code.txt".to_string());
-//     let path_authentications =
-//         env::var("PATH_AUTHENTICATIONS").unwrap_or("/tmp/twba/auth/{user}.json".to_string());
-//     let use_file_auth_response =
-//         env::var("USE_FILE_AUTH_RESPONSE").unwrap_or("1".to_string()) == "1";
-//     let use_local_auth_redirect =
-//         env::var("USE_LOCAL_AUTH_REDIRECT").unwrap_or("0".to_string()) == "1";
-//     let auth_file_read_timeout = env::var("AUTH_FILE_READ_TIMEOUT")
-//         .unwrap_or("5".to_string())
-//         .parse()
-//         .unwrap();
-//     Config {
-//         path_auth_code,
-//         use_file_auth_response,
-//         path_authentications,
-//         use_local_auth_redirect,
-//         auth_file_read_timeout,
-//     }
-// }
+use std::env;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::auth_code::{AuthCodeProvider, FileCodeProvider, StdinCodeProvider};
+use crate::redirect_server::LocalRedirectServer;
+use crate::{PrivacyStatus, VideoCategory, VideoMetadataBuilder, YoutubeError};
+
+/// Where the auth code of the installed flow comes from, unless an
+/// [`AuthCodeProvider`] is set on the builder.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthCodeSource {
+    Stdin,
+    /// The first line of [`YoutubeConfig::path_auth_code`].
+    #[default]
+    File,
+    /// The embedded redirect server on [`YoutubeConfig::local_redirect_port`].
+    LocalRedirect,
+}
+
+/// Everything about the client that used to come from environment variables.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct YoutubeConfig {
+    /// The redirect uri of the installed flow, not used with [`AuthCodeSource::LocalRedirect`].
+    pub redirect_uri: String,
+    pub auth_code_source: AuthCodeSource,
+    pub path_auth_code: PathBuf,
+    /// How often the code file is checked.
+    #[serde(with = "seconds")]
+    pub auth_file_poll_interval: Duration,
+    /// Give up waiting for the auth code after this time, waits forever if not set.
+    #[serde(with = "optional_seconds")]
+    pub auth_code_timeout: Option<Duration>,
+    pub local_redirect_port: u16,
+    pub local_redirect_path: String,
+    /// Where the tokens are persisted, `{user}` is replaced with the name of the user.
+    pub path_authentications: String,
+    pub default_privacy: PrivacyStatus,
+    pub default_category: VideoCategory,
+    /// The endpoint [`YoutubeClient::token_info`](crate::YoutubeClient::token_info) asks.
+    pub token_info_url: String,
+    /// The endpoint [`YoutubeClient::logout`](crate::YoutubeClient::logout) revokes the tokens at.
+    pub revoke_url: String,
+}
+
+impl Default for YoutubeConfig {
+    fn default() -> Self {
+        Self {
+            redirect_uri: "https://game-omgeeky.de:7443/googleapi/auth".to_string(),
+            auth_code_source: AuthCodeSource::default(),
+            path_auth_code: PathBuf::from("/tmp/twba/auth/code.txt"),
+            auth_file_poll_interval: Duration::from_secs(5),
+            auth_code_timeout: None,
+            local_redirect_port: 8080,
+            local_redirect_path: "/googleapi/auth".to_string(),
+            path_authentications: "/tmp/twba/auth/{user}.json".to_string(),
+            default_privacy: PrivacyStatus::Private,
+            default_category: VideoCategory::default(),
+            token_info_url: "https://oauth2.googleapis.com/tokeninfo".to_string(),
+            revoke_url: "https://oauth2.googleapis.com/revoke".to_string(),
+        }
+    }
+}
+
+fn invalid_env(name: &str, e: impl std::fmt::Display) -> YoutubeError {
+    YoutubeError::Config(format!("invalid value for {}: {}", name, e))
+}
+
+impl YoutubeConfig {
+    /// Reads the config from the environment variables that were used before
+    /// the config existed, anything that is not set keeps its default.
+    ///
+    /// `PATH_AUTH_CODE`, `PATH_AUTHENTICATIONS`, `USE_FILE_AUTH_RESPONSE`,
+    /// `USE_LOCAL_AUTH_REDIRECT` and `AUTH_FILE_READ_TIMEOUT` (seconds).
+    pub fn from_env() -> Result<Self, YoutubeError> {
+        let mut config = Self::default();
+        if let Ok(path) = env::var("PATH_AUTH_CODE") {
+            config.path_auth_code = PathBuf::from(path);
+        }
+        if let Ok(path) = env::var("PATH_AUTHENTICATIONS") {
+            config.path_authentications = path;
+        }
+        if let Ok(value) = env::var("USE_FILE_AUTH_RESPONSE") {
+            config.auth_code_source = if value == "1" {
+                AuthCodeSource::File
+            } else {
+                AuthCodeSource::Stdin
+            };
+        }
+        if env::var("USE_LOCAL_AUTH_REDIRECT").as_deref() == Ok("1") {
+            config.redirect_uri = format!(
+                "http://localhost:{}{}",
+                config.local_redirect_port, config.local_redirect_path
+            );
+        }
+        if let Ok(value) = env::var("AUTH_FILE_READ_TIMEOUT") {
+            let seconds = value
+                .parse()
+                .map_err(|e| invalid_env("AUTH_FILE_READ_TIMEOUT", e))?;
+            config.auth_file_poll_interval = Duration::from_secs(seconds);
+        }
+        Ok(config)
+    }
+
+    /// Parses a config in toml format, missing keys keep their default.
+    pub fn from_toml_str(content: &str) -> Result<Self, YoutubeError> {
+        toml::from_str(content).map_err(|e| YoutubeError::Config(e.to_string()))
+    }
+
+    pub fn from_toml_file(path: impl AsRef<Path>) -> Result<Self, YoutubeError> {
+        let content = std::fs::read_to_string(path)?;
+        Self::from_toml_str(&content)
+    }
+
+    /// A metadata builder that starts with the default privacy and category of the config.
+    pub fn metadata_builder(&self, title: impl Into<String>) -> VideoMetadataBuilder {
+        VideoMetadataBuilder::new(title)
+            .privacy(self.default_privacy)
+            .category(self.default_category.clone())
+    }
+
+    pub(crate) fn local_redirect_server(&self) -> LocalRedirectServer {
+        let server =
+            LocalRedirectServer::new(self.local_redirect_port).path(&self.local_redirect_path);
+        match self.auth_code_timeout {
+            Some(timeout) => server.timeout(timeout),
+            None => server,
+        }
+    }
+
+    /// The provider for the configured [`AuthCodeSource`].
+    pub(crate) fn code_provider(&self) -> Arc<dyn AuthCodeProvider> {
+        match self.auth_code_source {
+            AuthCodeSource::Stdin => Arc::new(StdinCodeProvider),
+            AuthCodeSource::File => {
+                let provider = FileCodeProvider::new(&self.path_auth_code)
+                    .poll_interval(self.auth_file_poll_interval);
+                match self.auth_code_timeout {
+                    Some(timeout) => Arc::new(provider.timeout(timeout)),
+                    None => Arc::new(provider),
+                }
+            }
+            AuthCodeSource::LocalRedirect => Arc::new(self.local_redirect_server()),
+        }
+    }
+}
+
+mod seconds {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::time::Duration;
+
+    pub fn serialize<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(duration.as_secs())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        u64::deserialize(deserializer).map(Duration::from_secs)
+    }
+}
+
+mod optional_seconds {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::time::Duration;
+
+    pub fn serialize<S: Serializer>(
+        duration: &Option<Duration>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match duration {
+            Some(duration) => serializer.serialize_some(&duration.as_secs()),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Duration>, D::Error> {
+        Option::<u64>::deserialize(deserializer).map(|secs| secs.map(Duration::from_secs))
+    }
+}
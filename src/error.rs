@@ -0,0 +1,211 @@
+use chrono::{DateTime, Utc};
+use std::fmt::Display;
+
+use google_youtube3::{
+    self as youtube,
+    hyper::{self, Body, Response, StatusCode},
+};
+use serde_json::Value;
+
+use crate::quota::QuotaOperation;
+use crate::scopes::Scope;
+use crate::thumbnail::ThumbnailError;
+use crate::validation::ValidationError;
+
+/// Errors returned by all [`YoutubeClient`](crate::YoutubeClient) methods.
+#[derive(Debug, thiserror::Error)]
+pub enum YoutubeError {
+    #[error("the youtube api quota has been exceeded: {0}")]
+    QuotaExceeded(#[source] youtube::Error),
+    #[error("the request was not authorized: {0}")]
+    Unauthorized(#[source] youtube::Error),
+    #[error("the requested resource was not found: {0}")]
+    NotFound(#[source] youtube::Error),
+    #[error("file io error: {0}")]
+    FileIo(#[from] std::io::Error),
+    #[error("the upload was rejected: {reason}")]
+    UploadRejected {
+        reason: String,
+        #[source]
+        source: youtube::Error,
+    },
+    #[error("backoff error: {0}")]
+    Backoff(String),
+    #[error("got status {status}: {body}")]
+    Api {
+        status: u16,
+        body: String,
+        #[source]
+        source: Option<youtube::Error>,
+    },
+    #[error("the video {0} does not exist")]
+    VideoNotFound(String),
+    #[error("the playlist {0} does not exist")]
+    PlaylistNotFound(String),
+    #[error("the authenticated account has no channel")]
+    ChannelNotFound,
+    #[error("authenticated as channel {actual} instead of {expected}")]
+    WrongChannel { expected: String, actual: String },
+    #[error("the {0} has no id")]
+    MissingId(&'static str),
+    #[error("invalid title pattern: {0}")]
+    InvalidPattern(#[source] regex::Error),
+    #[error("unknown video category: {0}")]
+    UnknownCategory(String),
+    #[error("the publish time {0} is not in the future")]
+    InvalidPublishTime(DateTime<Utc>),
+    #[error("invalid video metadata: {}", join_errors(.0))]
+    Validation(Vec<ValidationError>),
+    #[error("invalid thumbnail: {0}")]
+    InvalidThumbnail(#[from] ThumbnailError),
+    #[error("{operation} costs {cost} units but only {remaining} are left of the quota budget")]
+    QuotaBudgetExceeded {
+        operation: QuotaOperation,
+        cost: u64,
+        remaining: u64,
+    },
+    #[error("no user has enough quota left for {0}")]
+    PoolExhausted(QuotaOperation),
+    #[error("unknown youtube scope: {0}")]
+    UnknownScope(String),
+    #[error("the client is not authorized for the scope {0}")]
+    MissingScope(Scope),
+    #[error("the client does not keep its tokens in a token store")]
+    NoTokenStore,
+    #[error("invalid configuration: {0}")]
+    Config(String),
+    #[error("could not set up authentication: {0}")]
+    Auth(#[source] Box<dyn std::error::Error + Send + Sync>),
+}
+
+impl YoutubeError {
+    pub(crate) fn backoff(e: impl Display) -> Self {
+        YoutubeError::Backoff(e.to_string())
+    }
+
+    /// Error for a response that made it through the backoff but still has no success status.
+    pub(crate) async fn status(response: Response<Body>) -> Self {
+        let status = response.status().as_u16();
+        let body = hyper::body::to_bytes(response.into_body())
+            .await
+            .map(|bytes| String::from_utf8_lossy(&bytes).into_owned())
+            .unwrap_or_default();
+        YoutubeError::Api {
+            status,
+            body,
+            source: None,
+        }
+    }
+
+    /// Unwraps the result of a call that went through the backoff, see [`Self::from_api`].
+    pub(crate) async fn api<T>(
+        result: Result<youtube::Result<T>, impl Display>,
+    ) -> Result<T, Self> {
+        match result.map_err(YoutubeError::backoff)? {
+            Ok(value) => Ok(value),
+            Err(e) => Err(YoutubeError::from_api(e).await),
+        }
+    }
+
+    /// Like the [`From`] conversion, but the body of a failed response is kept.
+    pub(crate) async fn from_api(e: youtube::Error) -> Self {
+        match e {
+            youtube::Error::Failure(response) => {
+                let (parts, body) = response.into_parts();
+                let bytes = hyper::body::to_bytes(body).await.unwrap_or_default();
+                let body = String::from_utf8_lossy(&bytes).into_owned();
+                // the response goes back into the source, with the body that was read
+                let e = youtube::Error::Failure(Response::from_parts(parts, Body::from(bytes)));
+                match YoutubeError::from(e) {
+                    YoutubeError::Api { status, source, .. } => YoutubeError::Api {
+                        status,
+                        body,
+                        source,
+                    },
+                    e => e,
+                }
+            }
+            e => YoutubeError::from(e),
+        }
+    }
+
+    pub(crate) fn auth(e: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> Self {
+        YoutubeError::Auth(e.into())
+    }
+
+    /// Like the [`From`] conversion, but any rejected request is reported as [`YoutubeError::UploadRejected`].
+    pub(crate) async fn from_upload(e: youtube::Error) -> Self {
+        match YoutubeError::from_api(e).await {
+            YoutubeError::Api {
+                status,
+                body,
+                source: Some(source),
+            } if status == 400 || status == 403 => {
+                let reason = match &source {
+                    youtube::Error::BadRequest(value) => error_reason(value),
+                    _ => None,
+                }
+                .unwrap_or(body);
+                YoutubeError::UploadRejected { reason, source }
+            }
+            e => e,
+        }
+    }
+}
+
+fn join_errors(errors: &[ValidationError]) -> String {
+    errors
+        .iter()
+        .map(|e| e.to_string())
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+/// Extracts the reason of the first error in a google api error response.
+fn error_reason(value: &Value) -> Option<String> {
+    value["error"]["errors"][0]["reason"]
+        .as_str()
+        .map(|s| s.to_string())
+}
+
+fn error_code(value: &Value) -> Option<u16> {
+    value["error"]["code"].as_u64().map(|c| c as u16)
+}
+
+impl From<youtube::Error> for YoutubeError {
+    fn from(e: youtube::Error) -> Self {
+        match e {
+            youtube::Error::Io(e) => YoutubeError::FileIo(e),
+            youtube::Error::MissingToken(_) => YoutubeError::Unauthorized(e),
+            youtube::Error::BadRequest(ref value) => {
+                let status = error_code(value).unwrap_or(400);
+                match (status, error_reason(value).as_deref()) {
+                    (_, Some("quotaExceeded" | "dailyLimitExceeded")) => {
+                        YoutubeError::QuotaExceeded(e)
+                    }
+                    (401, _) => YoutubeError::Unauthorized(e),
+                    (404, _) => YoutubeError::NotFound(e),
+                    _ => YoutubeError::Api {
+                        status,
+                        body: value.to_string(),
+                        source: Some(e),
+                    },
+                }
+            }
+            youtube::Error::Failure(ref response) => match response.status() {
+                StatusCode::UNAUTHORIZED => YoutubeError::Unauthorized(e),
+                StatusCode::NOT_FOUND => YoutubeError::NotFound(e),
+                status => YoutubeError::Api {
+                    status: status.as_u16(),
+                    body: String::new(),
+                    source: Some(e),
+                },
+            },
+            e => YoutubeError::Api {
+                status: 0,
+                body: String::new(),
+                source: Some(e),
+            },
+        }
+    }
+}
@@ -1,10 +1,13 @@
-use anyhow::{anyhow, Context};
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
 use std::default::Default;
 use std::error::Error;
 use std::fmt::{Debug, Formatter};
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 use exponential_backoff::youtube::generic_check_backoff_youtube;
+use futures::Stream;
 use google_youtube3::{
     self as youtube,
     api::Playlist,
@@ -15,124 +18,254 @@ use google_youtube3::{
     api::PlaylistStatus,
     api::ResourceId,
     api::Video,
-    api::VideoSnippet,
-    api::VideoStatus,
     hyper::{client::HttpConnector, Body, Response},
     hyper_rustls::HttpsConnector,
+    oauth2::authenticator::Authenticator,
 };
+use serde::{Deserialize, Serialize};
+use tokio::sync::watch;
 #[cfg(feature = "tracing")]
 use tracing::instrument;
 use youtube::YouTube;
-use youtube::{hyper, hyper_rustls::HttpsConnectorBuilder};
 
+use crate::channel::OwnedRequest;
+use crate::pagination::{paginate, PageRequest, MAX_RESULTS};
+use crate::playlist_cache::PlaylistCache;
 use crate::prelude::*;
+use crate::progress::ProgressReader;
+use crate::scopes::{Scope, ScopeSet};
+use crate::upload_session::{is_expired_session, UploadSessionDelegate, UploadSessionStore};
+use crate::validation::{check_video, ValidationMode};
 
 mod auth;
+mod auth_code;
+mod builder;
+mod category;
+mod channel;
+mod config;
+mod error;
+mod metadata;
+mod pagination;
+mod playlist_cache;
+mod playlists;
+mod pool;
+mod progress;
+mod quota;
+mod redirect_server;
+mod thumbnail;
+mod token;
+mod token_store;
+mod upload_session;
+pub mod validation;
+mod videos;
+pub use auth::AuthMode;
+pub use auth_code::{
+    AuthCodeError, AuthCodeProvider, AuthCodeRequest, ChannelCodeProvider, FileCodeProvider,
+    StdinCodeProvider,
+};
+pub use builder::YoutubeClientBuilder;
+pub use category::{CategoryInfo, CategoryRegistry, VideoCategory};
+pub use channel::{ChannelInfo, ContentOwner, LongUploadsStatus};
+pub use config::{AuthCodeSource, YoutubeConfig};
+pub use error::YoutubeError;
+pub use metadata::{VideoLicense, VideoMetadata, VideoMetadataBuilder, VideoPatch};
+pub use playlists::{AddToPlaylistOptions, AsId, PlaylistPatch, TitleMatcher};
+pub use pool::YoutubeClientPool;
+pub use progress::UploadProgress;
+pub use quota::{QuotaOperation, QuotaPolicy, QuotaTracker, QuotaUsage, DEFAULT_DAILY_QUOTA};
+pub use redirect_server::LocalRedirectServer;
+pub use thumbnail::{
+    ThumbnailError, ThumbnailFormat, MAX_THUMBNAIL_BYTES, RECOMMENDED_THUMBNAIL_SIZE,
+};
+pub use token::{AccessTokenInfo, TokenEvent};
+#[cfg(feature = "sqlite")]
+pub use token_store::SqliteTokenStore;
+#[cfg(feature = "token-encryption")]
+pub use token_store::{EncryptedFileTokenStore, EncryptionKey};
+pub use token_store::{FileTokenStore, MemoryTokenStore, TokenStore};
 pub mod prelude;
 pub mod scopes;
-// mod config;
 
 pub struct YoutubeClient {
     pub client: YouTube<HttpsConnector<HttpConnector>>,
+    upload_sessions: Option<UploadSessionStore>,
+    validation_mode: ValidationMode,
+    categories: tokio::sync::Mutex<HashMap<String, CategoryRegistry>>,
+    playlist_cache: Option<PlaylistCache>,
+    quota: QuotaTracker,
+    config: YoutubeConfig,
+    authenticator: Authenticator<HttpsConnector<HttpConnector>>,
+    scopes: std::sync::Mutex<ScopeSet>,
+    /// Scopes that are not youtube scopes, they are kept when more scopes are requested.
+    extra_scopes: Vec<String>,
+    incremental_auth: bool,
+    token_events: tokio::sync::broadcast::Sender<TokenEvent>,
+    user: String,
+    /// Only set if the authenticator keeps its tokens in the store.
+    token_store: Option<Arc<dyn TokenStore>>,
+    content_owner: Option<ContentOwner>,
+    /// Whether the authenticator can get a new access token on its own.
+    refreshable: bool,
 }
 impl Debug for YoutubeClient {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("YoutubeClient").finish()
     }
 }
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum PrivacyStatus {
     Public,
     Unlisted,
     Private,
+    /// Private until youtube publishes the video at the given time.
+    ///
+    /// Playlists can not be scheduled, they are created as private.
+    Scheduled(DateTime<Utc>),
 }
 impl PrivacyStatus {
     fn to_string(&self) -> String {
         match self {
             PrivacyStatus::Public => "public".to_string(),
             PrivacyStatus::Unlisted => "unlisted".to_string(),
-            PrivacyStatus::Private => "private".to_string(),
+            PrivacyStatus::Private | PrivacyStatus::Scheduled(_) => "private".to_string(),
+        }
+    }
+
+    pub fn publish_at(&self) -> Option<DateTime<Utc>> {
+        match self {
+            PrivacyStatus::Scheduled(when) => Some(*when),
+            _ => None,
+        }
+    }
+
+    /// Makes sure a scheduled publish time is in the future.
+    fn validate(&self) -> Result<(), YoutubeError> {
+        match self.publish_at() {
+            Some(when) if when <= Utc::now() => Err(YoutubeError::InvalidPublishTime(when)),
+            _ => Ok(()),
         }
     }
 }
 impl YoutubeClient {
+    pub fn builder() -> YoutubeClientBuilder {
+        YoutubeClientBuilder::new()
+    }
+
+    pub fn config(&self) -> &YoutubeConfig {
+        &self.config
+    }
+
+    /// The youtube scopes the client is authorized for.
+    pub fn scopes(&self) -> ScopeSet {
+        self.scopes.lock().unwrap().clone()
+    }
+
+    /// Makes sure the client is authorized for the scope.
+    ///
+    /// With incremental authorization a missing scope is requested together with
+    /// the current ones, which can mean the user has to authorize the application again.
+    #[cfg_attr(feature = "tracing", instrument)]
+    pub async fn require_scope(&self, scope: Scope) -> Result<(), YoutubeError> {
+        let mut scopes = self.scopes();
+        if scopes.allows(scope) {
+            return Ok(());
+        }
+        if !self.incremental_auth {
+            return Err(YoutubeError::MissingScope(scope));
+        }
+        info!("requesting the additional scope {}", scope);
+        scopes.insert(scope);
+        let mut urls = scopes.urls();
+        urls.extend(self.extra_scopes.iter().map(String::as_str));
+        self.authenticator
+            .token(&urls)
+            .await
+            .map_err(YoutubeError::auth)?;
+        *self.scopes.lock().unwrap() = scopes;
+        Ok(())
+    }
+
+    /// Everything that has to happen before an api call: checking the scope and charging the quota.
+    pub(crate) async fn prepare_call(
+        &self,
+        scope: Scope,
+        operation: QuotaOperation,
+    ) -> Result<(), YoutubeError> {
+        self.require_scope(scope).await?;
+        self.quota.reserve(operation).await
+    }
+
+    /// The quota units this client used today and what is left of its budget.
+    pub fn quota(&self) -> &QuotaTracker {
+        &self.quota
+    }
+
     #[cfg_attr(feature = "tracing", instrument)]
     pub async fn new(
         path_to_application_secret: Option<impl Into<String> + Debug>,
         scopes: Vec<impl Into<String> + Debug>,
         user: Option<impl Into<String> + Debug>,
-    ) -> anyhow::Result<Self> {
-        let scopes = scopes
-            .into_iter()
-            .map(|s| s.into())
-            .collect::<Vec<String>>();
-        let hyper_client = hyper::Client::builder().build(
-            HttpsConnectorBuilder::new()
-                .with_native_roots()
-                .https_or_http()
-                .enable_http1()
-                .enable_http2()
-                .build(),
-        );
-
+    ) -> Result<Self, YoutubeError> {
         let path_to_application_secret = path_to_application_secret
             .map(|x| x.into())
             .unwrap_or_else(|| {
                 warn!("the path to the application secret was not provided. Using default!");
                 "auth/service_account2.json".to_string()
             });
-        trace!(
-            "getting authenticator from path: {}",
-            path_to_application_secret
-        );
-        let auth = auth::get_authenticator(path_to_application_secret, &scopes, user)
-            .await
-            .map_err(|e| anyhow!("error while getting authenticator: {}", e))?;
-        trace!("creating youtube client");
-        let client: YouTube<HttpsConnector<HttpConnector>> = YouTube::new(hyper_client, auth);
-
-        let res = Self { client };
-        Ok(res)
+        // keeps reading the environment variables callers of `new` rely on
+        let mut builder = Self::builder()
+            .application_secret_path(path_to_application_secret)
+            .scopes(scopes)
+            .config(YoutubeConfig::from_env()?);
+        if let Some(user) = user {
+            builder = builder.user(user);
+        }
+        builder.build().await
     }
 
-    #[cfg_attr(feature = "tracing", instrument)]
-    pub async fn find_playlist_by_name(&self, name: &str) -> Result<Option<Playlist>> {
-        let part = vec!["snippet".to_string()];
-
+    /// Lists all playlists of the authenticated user, walking all result pages.
+    ///
+    /// Acting for a content owner, these are the playlists of its channel.
+    pub fn list_my_playlists(&self) -> impl Stream<Item = Result<Playlist, YoutubeError>> + '_ {
+        #[derive(Debug, Clone)]
         struct PlaylistParams {
             part: Vec<String>,
-            mine: bool,
+            content_owner: Option<ContentOwner>,
         }
         async fn list_playlist(
             client: &YouTube<HttpsConnector<HttpConnector>>,
-            params: &PlaylistParams,
+            request: PageRequest<PlaylistParams>,
         ) -> google_youtube3::Result<(Response<Body>, PlaylistListResponse)> {
-            client
+            let mut call = client
                 .playlists()
-                .list(&params.part)
-                .mine(params.mine)
-                .doit()
-                .await
-        }
-        let para = PlaylistParams { part, mine: true };
-        let (_res, playlists): (Response<Body>, PlaylistListResponse) =
-            generic_check_backoff_youtube(&self.client, &para, list_playlist)
-                .await
-                .map_err(|e| anyhow!("backoff error: {}", e))?
-                .context("list_playlist returned an error")?;
-
-        if let Some(items) = playlists.items {
-            for element in items {
-                if let Some(snippet) = &element.snippet {
-                    if let Some(title) = &snippet.title {
-                        if title == name {
-                            return Ok(Some(element));
-                        }
-                    }
-                }
+                .list(&request.params.part)
+                .max_results(MAX_RESULTS);
+            call = match &request.params.content_owner {
+                Some(owner) => call
+                    .on_behalf_of_content_owner(&owner.owner)
+                    .channel_id(&owner.channel),
+                None => call.mine(true),
+            };
+            if let Some(page_token) = &request.page_token {
+                call = call.page_token(page_token);
             }
+            call.doit().await
         }
-        Ok(None)
+        let para = PlaylistParams {
+            part: vec!["snippet".to_string(), "status".to_string()],
+            content_owner: self.content_owner.clone(),
+        };
+        paginate(self, para, list_playlist)
+    }
+
+    #[cfg_attr(feature = "tracing", instrument)]
+    pub async fn find_playlist_by_name(
+        &self,
+        name: &str,
+    ) -> Result<Option<Playlist>, YoutubeError> {
+        self.find_playlist(&TitleMatcher::Exact(name.to_string()))
+            .await
     }
 
     #[cfg_attr(feature = "tracing", instrument)]
@@ -140,7 +273,7 @@ impl YoutubeClient {
         &self,
         name: &str,
         privacy: PrivacyStatus,
-    ) -> Result<Playlist> {
+    ) -> Result<Playlist, YoutubeError> {
         let playlist = self.find_playlist_by_name(name).await?;
         if let Some(playlist) = playlist {
             return Ok(playlist);
@@ -150,44 +283,83 @@ impl YoutubeClient {
     }
 
     #[cfg_attr(feature = "tracing", instrument)]
-    pub async fn add_video_to_playlist(&self, video: &Video, playlist: &Playlist) -> Result<()> {
+    pub async fn add_video_to_playlist(
+        &self,
+        video: &(impl AsId + ?Sized + Debug),
+        playlist: &(impl AsId + ?Sized + Debug),
+    ) -> Result<(), YoutubeError> {
+        self.add_video_to_playlist_with(video, playlist, &AddToPlaylistOptions::default())
+            .await?;
+        Ok(())
+    }
+
+    /// Adds the video to the playlist and returns the playlist item.
+    ///
+    /// With [`AddToPlaylistOptions::skip_if_present`] the existing item is
+    /// returned if the video already is in the playlist.
+    #[cfg_attr(feature = "tracing", instrument)]
+    pub async fn add_video_to_playlist_with(
+        &self,
+        video: &(impl AsId + ?Sized + Debug),
+        playlist: &(impl AsId + ?Sized + Debug),
+        options: &AddToPlaylistOptions,
+    ) -> Result<PlaylistItem, YoutubeError> {
+        let video_id = video.as_id().ok_or(YoutubeError::MissingId("video"))?;
+        let playlist_id = playlist
+            .as_id()
+            .ok_or(YoutubeError::MissingId("playlist"))?;
+
+        if options.skip_if_present {
+            if let Some(item) = self.find_playlist_item(playlist_id, video_id).await? {
+                debug!(
+                    "video {} is already in playlist {}, skipping",
+                    video_id, playlist_id
+                );
+                return Ok(item);
+            }
+        }
+
         let playlist_item = PlaylistItem {
             snippet: Some(PlaylistItemSnippet {
-                playlist_id: Some(playlist.id.clone().unwrap()),
+                playlist_id: Some(playlist_id.to_string()),
                 resource_id: Some(ResourceId {
                     kind: Some("youtube#video".to_string()),
-                    video_id: Some(video.id.clone().unwrap()),
+                    video_id: Some(video_id.to_string()),
                     ..Default::default()
                 }),
+                position: options.position,
                 ..Default::default()
             }),
             ..Default::default()
         };
         async fn insert_playlist_item(
             client: &YouTube<HttpsConnector<HttpConnector>>,
-            playlist_item: &PlaylistItem,
+            request: &OwnedRequest<PlaylistItem>,
         ) -> google_youtube3::Result<(Response<Body>, PlaylistItem)> {
-            client
-                .playlist_items()
-                .insert(playlist_item.clone())
-                .doit()
-                .await
+            let mut call = client.playlist_items().insert(request.body.clone());
+            if let Some(owner) = &request.content_owner {
+                call = call.on_behalf_of_content_owner(&owner.owner);
+            }
+            call.doit().await
         }
 
         // let res = self.client.playlist_items().insert(playlist_item).doit().await?;
 
-        let (res, _) =
-            generic_check_backoff_youtube(&self.client, &playlist_item, insert_playlist_item)
-                .await
-                .map_err(|e| anyhow!("backoff error: {}", e))?
-                .context("insert playlist item returned an error")?;
+        let request = self.owned_request(playlist_item);
+        self.prepare_call(Scope::Full, QuotaOperation::Insert)
+            .await?;
+        let (res, item) = YoutubeError::api(
+            generic_check_backoff_youtube(&self.client, &request, insert_playlist_item).await,
+        )
+        .await?;
         if res.status().is_success() {
-            Ok(())
+            Ok(item)
         } else {
-            Err(anyhow!("got status: {}", res.status().as_u16()))
+            Err(YoutubeError::status(res).await)
         }
     }
 
+    /// Uploads a video into the default category of the config.
     #[cfg_attr(feature = "tracing", instrument)]
     pub async fn upload_video(
         &self,
@@ -196,41 +368,84 @@ impl YoutubeClient {
         description: impl Into<String> + Debug,
         tags: impl Into<Vec<String>> + Debug,
         privacy_status: PrivacyStatus,
-    ) -> Result<Video> {
-        info!("test 123");
-        let video = Video {
-            snippet: Some(VideoSnippet {
-                title: Some(title.into()),
-                description: Some(description.into()),
-                category_id: Some("20".to_string()),
-                tags: Some(tags.into()),
-                ..Default::default()
-            }),
+    ) -> Result<Video, YoutubeError> {
+        let metadata = self
+            .config
+            .metadata_builder(title)
+            .description(description)
+            .tags(tags)
+            .privacy(privacy_status)
+            .build();
+        self.upload(path.as_ref(), &metadata, None).await
+    }
 
-            status: Some(VideoStatus {
-                privacy_status: Some(privacy_status.to_string()),
-                public_stats_viewable: Some(true),
-                embeddable: Some(true),
-                self_declared_made_for_kids: Some(false),
-                ..Default::default()
-            }),
-            ..Default::default()
-        };
+    /// Uploads a video with all the metadata from [`VideoMetadata`].
+    ///
+    /// The metadata is used as is, start it with [`YoutubeConfig::metadata_builder`]
+    /// to get the default privacy and category of the config.
+    #[cfg_attr(feature = "tracing", instrument)]
+    pub async fn upload_video_with(
+        &self,
+        path: impl AsRef<Path> + Debug,
+        metadata: &VideoMetadata,
+    ) -> Result<Video, YoutubeError> {
+        self.upload(path.as_ref(), metadata, None).await
+    }
+
+    /// Same as [`Self::upload_video_with`] but reports the progress of the upload to the given channel.
+    ///
+    /// The sender is dropped once the upload is done, so receivers can wait on
+    /// [`watch::Receiver::changed`] until it returns an error.
+    #[cfg_attr(feature = "tracing", instrument(skip(progress)))]
+    pub async fn upload_video_with_progress(
+        &self,
+        path: impl AsRef<Path> + Debug,
+        metadata: &VideoMetadata,
+        progress: watch::Sender<UploadProgress>,
+    ) -> Result<Video, YoutubeError> {
+        self.upload(path.as_ref(), metadata, Some(&progress)).await
+    }
+
+    #[cfg_attr(feature = "tracing", instrument(skip(progress)))]
+    async fn upload(
+        &self,
+        path: &Path,
+        metadata: &VideoMetadata,
+        progress: Option<&watch::Sender<UploadProgress>>,
+    ) -> Result<Video, YoutubeError> {
+        metadata.privacy_status.validate()?;
+        let category_id = self
+            .resolve_category(&metadata.category, metadata.region_code.as_deref())
+            .await?;
+        let mut video = metadata.to_video(category_id);
+        check_video(self.validation_mode, &mut video).map_err(YoutubeError::Validation)?;
         // let file = file.into_std().await;
 
-        struct UploadParameters {
+        struct UploadParameters<'a> {
             video: Video,
+            notify_subscribers: bool,
             path: PathBuf,
+            progress: Option<&'a watch::Sender<UploadProgress>>,
+            sessions: Option<(&'a UploadSessionStore, String)>,
+            content_owner: Option<&'a ContentOwner>,
         }
 
+        let sessions = match &self.upload_sessions {
+            Some(store) => Some((store, UploadSessionStore::key_for(path)?)),
+            None => None,
+        };
         let params = UploadParameters {
-            video: video.clone(),
-            path: path.as_ref().into(),
+            video,
+            notify_subscribers: metadata.notify_subscribers,
+            path: path.into(),
+            progress,
+            sessions,
+            content_owner: self.content_owner.as_ref(),
         };
 
         async fn upload_fn(
             client: &YouTube<HttpsConnector<HttpConnector>>,
-            para: &UploadParameters,
+            para: &UploadParameters<'_>,
         ) -> Result<(Response<Body>, Video), google_youtube3::Error> {
             info!("Opening file: {:?}", para.path);
             let stream = std::fs::File::open(&para.path).map_err(|e| {
@@ -239,24 +454,75 @@ impl YoutubeClient {
             })?;
             info!("Uploading file: {:?}", para.path);
 
-            let insert_call = client.videos().insert(para.video.clone());
+            let mut delegate = para
+                .sessions
+                .as_ref()
+                .map(|(store, key)| UploadSessionDelegate::new(store, key.clone()));
+            let mut insert_call = client
+                .videos()
+                .insert(para.video.clone())
+                .notify_subscribers(para.notify_subscribers)
+                .add_scope(Scope::Upload);
+            if let Some(owner) = para.content_owner {
+                insert_call = insert_call
+                    .on_behalf_of_content_owner(&owner.owner)
+                    .on_behalf_of_content_owner_channel(&owner.channel);
+            }
+            if let Some(delegate) = delegate.as_mut() {
+                insert_call = insert_call.delegate(delegate);
+            }
             info!("Insert call created");
-            let upload_call = insert_call.upload_resumable(stream, "video/mp4".parse().unwrap());
-            // .upload(stream, "video/mp4".parse().unwrap());
             info!("Upload request");
-            let res = upload_call.await.map_err(|e| {
+            let res = match para.progress {
+                Some(progress) => {
+                    let stream = ProgressReader::new(stream, progress)?;
+                    insert_call
+                        .upload_resumable(stream, "video/mp4".parse().unwrap())
+                        .await
+                }
+                None => {
+                    insert_call
+                        .upload_resumable(stream, "video/mp4".parse().unwrap())
+                        .await
+                }
+            };
+            // .upload(stream, "video/mp4".parse().unwrap());
+            let res = res.map_err(|e| {
                 error!("upload call did not work: {}", e);
+                if let Some((store, key)) = &para.sessions {
+                    if is_expired_session(&e) {
+                        warn!("upload session expired, the next attempt starts over");
+                        store.remove(key);
+                    }
+                }
                 e
             });
             info!("Upload request done");
             res
         }
 
+        if let Some((store, key)) = &params.sessions {
+            self.require_scope(Scope::Upload).await?;
+            if let Some(video) = self.resume_upload(store, key, path, progress).await? {
+                store.remove(key);
+                info!("Upload successful!");
+                return Ok(video);
+            }
+        }
+
         info!("Starting upload...");
-        let (response, video) = generic_check_backoff_youtube(&self.client, &params, upload_fn)
+        self.prepare_call(Scope::Upload, QuotaOperation::VideoInsert)
+            .await?;
+        let result = generic_check_backoff_youtube(&self.client, &params, upload_fn)
             .await
-            .map_err(|e| anyhow!("backoff error: {}", e))?
-            .context("upload_fn returned an error")?;
+            .map_err(YoutubeError::backoff)?;
+        let (response, video) = match result {
+            Ok(result) => result,
+            Err(e) => return Err(YoutubeError::from_upload(e).await),
+        };
+        if let Some((store, key)) = &params.sessions {
+            store.remove(key);
+        }
 
         // let (response, video) = exponential_backoff::youtube::check_backoff_youtube_upload(
         //     &self.client,
@@ -274,7 +540,7 @@ impl YoutubeClient {
             info!("Status: {}", response.status());
             info!("Body: {:?}", response);
             info!("Video: {:?}", video);
-            Err(anyhow!("got status: {}", response.status().as_u16()))
+            Err(YoutubeError::status(response).await)
         }
 
         // return Ok(video);
@@ -293,8 +559,13 @@ impl YoutubeClient {
         //     }
         // }
     }
+
     #[cfg_attr(feature = "tracing", instrument)]
-    async fn create_playlist(&self, name: &str, privacy: PrivacyStatus) -> Result<Playlist> {
+    pub async fn create_playlist(
+        &self,
+        name: &str,
+        privacy: PrivacyStatus,
+    ) -> Result<Playlist, YoutubeError> {
         let playlist = Playlist {
             snippet: Some(PlaylistSnippet {
                 title: Some(name.to_string()),
@@ -308,21 +579,30 @@ impl YoutubeClient {
 
         async fn create_playlist(
             client: &YouTube<HttpsConnector<HttpConnector>>,
-            params: &Playlist,
+            request: &OwnedRequest<Playlist>,
         ) -> google_youtube3::Result<(Response<Body>, Playlist)> {
-            client.playlists().insert(params.clone()).doit().await
+            let mut call = client.playlists().insert(request.body.clone());
+            if let Some(owner) = &request.content_owner {
+                call = call
+                    .on_behalf_of_content_owner(&owner.owner)
+                    .on_behalf_of_content_owner_channel(&owner.channel);
+            }
+            call.doit().await
         }
 
-        let (res, playlist) =
-            generic_check_backoff_youtube(&self.client, &playlist, create_playlist)
-                .await
-                .map_err(|e| anyhow!("backoff error: {}", e))?
-                .map_err(|e| anyhow!("create playlist returned an error: {}", e))?;
+        let request = self.owned_request(playlist);
+        self.prepare_call(Scope::Full, QuotaOperation::Insert)
+            .await?;
+        let (res, playlist) = YoutubeError::api(
+            generic_check_backoff_youtube(&self.client, &request, create_playlist).await,
+        )
+        .await?;
 
+        self.invalidate_playlist_cache().await;
         if res.status().is_success() {
             Ok(playlist)
         } else {
-            Err(anyhow!("got status: {}", res.status().as_u16()))
+            Err(YoutubeError::status(res).await)
         }
     }
 }
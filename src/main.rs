@@ -5,8 +5,10 @@ use std::path::Path;
 use google_youtube3::api::Playlist;
 use simplelog::ColorChoice;
 use tokio::fs::File;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
 
-use google_youtube::{scopes, PrivacyStatus, YoutubeClient};
+use google_youtube::{scopes, PrivacyStatus, UploadProgress, VideoMetadata, YoutubeClient};
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
@@ -34,29 +36,18 @@ pub async fn sample() -> Result<(), Box<dyn Error>> {
     let user = "nopixelvods";
     let client = YoutubeClient::new(Some(client_secret_path), scopes, Some(user)).await?;
 
-    /*
-    // get list of channels of the authenticated user
-    let part = vec!["snippet".to_string()];
-    let (_res, channels) = client
-        .client
-        .channels()
-        .list(&part)
-        .mine(true)
-        .doit()
-        .await?;
-    for element in channels.items.unwrap() {
-        info!(
-            "channel name: {:?}",
-            element.snippet.unwrap().title.unwrap()
-        );
-    }
+    // make sure we are authenticated as the right channel before uploading
+    let channel = client.my_channel().await?;
+    info!(
+        "channel name: {:?}, subscribers: {:?}, long uploads: {:?}",
+        channel.title, channel.subscriber_count, channel.long_uploads
+    );
 
     info!("Channels done!\n\n");
-    */
 
     // get a playlist by name or create it if it does not exist('LunaOni Clips' for example)
     let playlist = client
-        .find_playlist_or_create_by_name("LunaOni Clips")
+        .find_playlist_or_create_by_name("LunaOni Clips", PrivacyStatus::Private)
         .await;
     info!("playlist: {:?}", playlist);
 
@@ -73,9 +64,17 @@ pub async fn sample() -> Result<(), Box<dyn Error>> {
         let privacy_status = PrivacyStatus::Private;
 
         info!("uploading video...");
+        let (progress, receiver) = watch::channel(UploadProgress::default());
+        let progress_handle = show_progress(receiver);
+        let metadata = VideoMetadata::builder(title)
+            .description(description)
+            .tags(tags)
+            .privacy(privacy_status)
+            .build();
         let insert = client
-            .upload_video(&path, title.as_str(), description, tags, privacy_status)
+            .upload_video_with_progress(&path, &metadata, progress)
             .await;
+        let _ = progress_handle.await;
         info!("uploading video... (done)");
 
         info!("adding to playlist...");
@@ -96,3 +95,45 @@ pub async fn sample() -> Result<(), Box<dyn Error>> {
     info!("Done!");
     Ok(())
 }
+
+#[cfg(feature = "indicatif")]
+fn show_progress(mut receiver: watch::Receiver<UploadProgress>) -> JoinHandle<()> {
+    use indicatif::{ProgressBar, ProgressStyle};
+    tokio::spawn(async move {
+        let bar = ProgressBar::new(0);
+        bar.set_style(
+            ProgressStyle::with_template(
+                "[{elapsed_precise}] [{bar:40}] {bytes}/{total_bytes} ({bytes_per_sec}, {eta})",
+            )
+            .unwrap(),
+        );
+        while receiver.changed().await.is_ok() {
+            let progress = *receiver.borrow();
+            bar.set_length(progress.total_bytes);
+            bar.set_position(progress.bytes_sent);
+        }
+        bar.finish();
+    })
+}
+
+#[cfg(not(feature = "indicatif"))]
+fn show_progress(mut receiver: watch::Receiver<UploadProgress>) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut last_percent = None;
+        while receiver.changed().await.is_ok() {
+            let progress = *receiver.borrow();
+            let percent = (progress.fraction() * 100.0) as u64;
+            if last_percent != Some(percent) {
+                last_percent = Some(percent);
+                info!(
+                    "upload progress: {}% ({}/{} bytes, {:.0} B/s, eta: {:?})",
+                    percent,
+                    progress.bytes_sent,
+                    progress.total_bytes,
+                    progress.bytes_per_second,
+                    progress.eta
+                );
+            }
+        }
+    })
+}
@@ -0,0 +1,344 @@
+use chrono::{DateTime, Utc};
+use google_youtube3::api::{Video, VideoRecordingDetails, VideoSnippet, VideoStatus};
+
+use crate::{PrivacyStatus, VideoCategory};
+
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum VideoLicense {
+    #[default]
+    Youtube,
+    CreativeCommon,
+}
+
+impl VideoLicense {
+    fn to_string(&self) -> String {
+        match self {
+            VideoLicense::Youtube => "youtube".to_string(),
+            VideoLicense::CreativeCommon => "creativeCommon".to_string(),
+        }
+    }
+}
+
+/// Everything that can be set on a video when uploading it.
+///
+/// Use [`VideoMetadata::builder`] to create one, every field that is not set
+/// falls back to a fixed default (private, gaming). To start from the defaults of
+/// the config like [`YoutubeClient::upload_video`](crate::YoutubeClient::upload_video)
+/// does, use [`YoutubeConfig::metadata_builder`](crate::YoutubeConfig::metadata_builder).
+///
+/// Whether the video contains synthetic (altered or AI generated) media can not be
+/// declared yet: the `VideoStatus` of the google-youtube3 version this crate is built
+/// on has no `containsSyntheticMedia` field. Set it in youtube studio until then.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VideoMetadata {
+    pub title: String,
+    pub description: String,
+    pub tags: Vec<String>,
+    pub privacy_status: PrivacyStatus,
+    pub category: VideoCategory,
+    /// The region the category is looked up in, the bundled categories are used if not set.
+    pub region_code: Option<String>,
+    pub default_language: Option<String>,
+    pub default_audio_language: Option<String>,
+    pub recording_date: Option<DateTime<Utc>>,
+    pub license: VideoLicense,
+    pub embeddable: bool,
+    pub public_stats_viewable: bool,
+    pub made_for_kids: bool,
+    /// Whether subscribers get notified about the new video.
+    pub notify_subscribers: bool,
+}
+
+impl VideoMetadata {
+    pub fn builder(title: impl Into<String>) -> VideoMetadataBuilder {
+        VideoMetadataBuilder::new(title)
+    }
+
+    pub(crate) fn to_video(&self, category_id: String) -> Video {
+        Video {
+            snippet: Some(VideoSnippet {
+                title: Some(self.title.clone()),
+                description: Some(self.description.clone()),
+                category_id: Some(category_id),
+                tags: Some(self.tags.clone()),
+                default_language: self.default_language.clone(),
+                default_audio_language: self.default_audio_language.clone(),
+                ..Default::default()
+            }),
+            status: Some(VideoStatus {
+                privacy_status: Some(self.privacy_status.to_string()),
+                publish_at: self.privacy_status.publish_at(),
+                license: Some(self.license.to_string()),
+                public_stats_viewable: Some(self.public_stats_viewable),
+                embeddable: Some(self.embeddable),
+                self_declared_made_for_kids: Some(self.made_for_kids),
+                ..Default::default()
+            }),
+            recording_details: self.recording_date.map(|date| VideoRecordingDetails {
+                recording_date: Some(date),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct VideoMetadataBuilder {
+    metadata: VideoMetadata,
+}
+
+impl VideoMetadataBuilder {
+    pub fn new(title: impl Into<String>) -> Self {
+        Self {
+            metadata: VideoMetadata {
+                title: title.into(),
+                description: String::new(),
+                tags: vec![],
+                privacy_status: PrivacyStatus::Private,
+                category: VideoCategory::default(),
+                region_code: None,
+                default_language: None,
+                default_audio_language: None,
+                recording_date: None,
+                license: VideoLicense::default(),
+                embeddable: true,
+                public_stats_viewable: true,
+                made_for_kids: false,
+                notify_subscribers: true,
+            },
+        }
+    }
+
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        self.metadata.description = description.into();
+        self
+    }
+
+    pub fn tags(mut self, tags: impl Into<Vec<String>>) -> Self {
+        self.metadata.tags = tags.into();
+        self
+    }
+
+    pub fn add_tag(mut self, tag: impl Into<String>) -> Self {
+        self.metadata.tags.push(tag.into());
+        self
+    }
+
+    pub fn privacy(mut self, privacy_status: PrivacyStatus) -> Self {
+        self.metadata.privacy_status = privacy_status;
+        self
+    }
+
+    pub fn category(mut self, category: VideoCategory) -> Self {
+        self.metadata.category = category;
+        self
+    }
+
+    pub fn category_id(self, category_id: impl Into<String>) -> Self {
+        self.category(VideoCategory::Id(category_id.into()))
+    }
+
+    pub fn region_code(mut self, region_code: impl Into<String>) -> Self {
+        self.metadata.region_code = Some(region_code.into());
+        self
+    }
+
+    pub fn default_language(mut self, language: impl Into<String>) -> Self {
+        self.metadata.default_language = Some(language.into());
+        self
+    }
+
+    pub fn default_audio_language(mut self, language: impl Into<String>) -> Self {
+        self.metadata.default_audio_language = Some(language.into());
+        self
+    }
+
+    pub fn recording_date(mut self, date: DateTime<Utc>) -> Self {
+        self.metadata.recording_date = Some(date);
+        self
+    }
+
+    pub fn license(mut self, license: VideoLicense) -> Self {
+        self.metadata.license = license;
+        self
+    }
+
+    pub fn embeddable(mut self, embeddable: bool) -> Self {
+        self.metadata.embeddable = embeddable;
+        self
+    }
+
+    pub fn public_stats_viewable(mut self, viewable: bool) -> Self {
+        self.metadata.public_stats_viewable = viewable;
+        self
+    }
+
+    pub fn made_for_kids(mut self, made_for_kids: bool) -> Self {
+        self.metadata.made_for_kids = made_for_kids;
+        self
+    }
+
+    /// Upload the video as private and let youtube publish it at the given time.
+    ///
+    /// Shorthand for `.privacy(PrivacyStatus::Scheduled(when))`.
+    pub fn publish_at(self, when: DateTime<Utc>) -> Self {
+        self.privacy(PrivacyStatus::Scheduled(when))
+    }
+
+    pub fn notify_subscribers(mut self, notify: bool) -> Self {
+        self.metadata.notify_subscribers = notify;
+        self
+    }
+
+    pub fn build(self) -> VideoMetadata {
+        self.metadata
+    }
+}
+
+/// A partial update of a video, only the fields that are set are changed.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct VideoPatch {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub tags: Option<Vec<String>>,
+    pub category: Option<VideoCategory>,
+    /// The region the category is looked up in, the bundled categories are used if not set.
+    pub region_code: Option<String>,
+    pub default_language: Option<String>,
+    pub default_audio_language: Option<String>,
+    pub recording_date: Option<DateTime<Utc>>,
+    pub privacy_status: Option<PrivacyStatus>,
+    pub license: Option<VideoLicense>,
+    pub embeddable: Option<bool>,
+    pub public_stats_viewable: Option<bool>,
+    pub made_for_kids: Option<bool>,
+}
+
+impl VideoPatch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn title(mut self, title: impl Into<String>) -> Self {
+        self.title = Some(title.into());
+        self
+    }
+
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    pub fn tags(mut self, tags: impl Into<Vec<String>>) -> Self {
+        self.tags = Some(tags.into());
+        self
+    }
+
+    pub fn category(mut self, category: VideoCategory) -> Self {
+        self.category = Some(category);
+        self
+    }
+
+    pub fn region_code(mut self, region_code: impl Into<String>) -> Self {
+        self.region_code = Some(region_code.into());
+        self
+    }
+
+    pub fn default_language(mut self, language: impl Into<String>) -> Self {
+        self.default_language = Some(language.into());
+        self
+    }
+
+    pub fn default_audio_language(mut self, language: impl Into<String>) -> Self {
+        self.default_audio_language = Some(language.into());
+        self
+    }
+
+    pub fn recording_date(mut self, date: DateTime<Utc>) -> Self {
+        self.recording_date = Some(date);
+        self
+    }
+
+    pub fn privacy(mut self, privacy_status: PrivacyStatus) -> Self {
+        self.privacy_status = Some(privacy_status);
+        self
+    }
+
+    pub fn license(mut self, license: VideoLicense) -> Self {
+        self.license = Some(license);
+        self
+    }
+
+    pub fn embeddable(mut self, embeddable: bool) -> Self {
+        self.embeddable = Some(embeddable);
+        self
+    }
+
+    pub fn public_stats_viewable(mut self, viewable: bool) -> Self {
+        self.public_stats_viewable = Some(viewable);
+        self
+    }
+
+    pub fn made_for_kids(mut self, made_for_kids: bool) -> Self {
+        self.made_for_kids = Some(made_for_kids);
+        self
+    }
+
+    pub(crate) fn changes_snippet(&self) -> bool {
+        self.title.is_some()
+            || self.description.is_some()
+            || self.tags.is_some()
+            || self.category.is_some()
+            || self.default_language.is_some()
+            || self.default_audio_language.is_some()
+    }
+
+    pub(crate) fn changes_status(&self) -> bool {
+        self.privacy_status.is_some()
+            || self.license.is_some()
+            || self.embeddable.is_some()
+            || self.public_stats_viewable.is_some()
+            || self.made_for_kids.is_some()
+    }
+
+    pub(crate) fn apply_to_snippet(&self, snippet: &mut VideoSnippet, category_id: Option<String>) {
+        if let Some(title) = &self.title {
+            snippet.title = Some(title.clone());
+        }
+        if let Some(description) = &self.description {
+            snippet.description = Some(description.clone());
+        }
+        if let Some(tags) = &self.tags {
+            snippet.tags = Some(tags.clone());
+        }
+        if let Some(category_id) = category_id {
+            snippet.category_id = Some(category_id);
+        }
+        if let Some(language) = &self.default_language {
+            snippet.default_language = Some(language.clone());
+        }
+        if let Some(language) = &self.default_audio_language {
+            snippet.default_audio_language = Some(language.clone());
+        }
+    }
+
+    pub(crate) fn apply_to_status(&self, status: &mut VideoStatus) {
+        if let Some(privacy_status) = &self.privacy_status {
+            status.privacy_status = Some(privacy_status.to_string());
+            status.publish_at = privacy_status.publish_at();
+        }
+        if let Some(license) = &self.license {
+            status.license = Some(license.to_string());
+        }
+        if let Some(embeddable) = self.embeddable {
+            status.embeddable = Some(embeddable);
+        }
+        if let Some(viewable) = self.public_stats_viewable {
+            status.public_stats_viewable = Some(viewable);
+        }
+        if let Some(made_for_kids) = self.made_for_kids {
+            status.self_declared_made_for_kids = Some(made_for_kids);
+        }
+    }
+}
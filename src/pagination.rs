@@ -0,0 +1,96 @@
+use std::future::Future;
+
+use exponential_backoff::youtube::generic_check_backoff_youtube;
+use futures::{stream, Stream, TryStreamExt};
+use google_youtube3::{
+    api::{Playlist, PlaylistItem, PlaylistItemListResponse, PlaylistListResponse},
+    hyper::{client::HttpConnector, Body, Response},
+    hyper_rustls::HttpsConnector,
+    YouTube,
+};
+
+use crate::quota::QuotaOperation;
+use crate::scopes::Scope;
+use crate::{YoutubeClient, YoutubeError};
+
+/// The maximum page size the youtube api allows for list calls.
+pub(crate) const MAX_RESULTS: u32 = 50;
+
+/// A single page of a list response.
+pub(crate) trait Page {
+    type Item;
+    /// Splits the page into its items and the token of the next page, if there is one.
+    fn into_page(self) -> (Vec<Self::Item>, Option<String>);
+}
+
+impl Page for PlaylistListResponse {
+    type Item = Playlist;
+    fn into_page(self) -> (Vec<Self::Item>, Option<String>) {
+        (self.items.unwrap_or_default(), self.next_page_token)
+    }
+}
+
+impl Page for PlaylistItemListResponse {
+    type Item = PlaylistItem;
+    fn into_page(self) -> (Vec<Self::Item>, Option<String>) {
+        (self.items.unwrap_or_default(), self.next_page_token)
+    }
+}
+
+/// The parameters of a list call together with the page that should be requested.
+#[derive(Debug, Clone)]
+pub(crate) struct PageRequest<P> {
+    pub params: P,
+    pub page_token: Option<String>,
+}
+
+/// Walks all pages of a list call, following the `next_page_token` of each response.
+///
+/// Every page is charged as a list call and requested through the backoff, pages are only requested
+/// once the items of the previous page have been consumed.
+pub(crate) fn paginate<'a, P, R, F, Fut>(
+    client: &'a YoutubeClient,
+    params: P,
+    fetch: F,
+) -> impl Stream<Item = Result<R::Item, YoutubeError>> + 'a
+where
+    P: Clone + 'a,
+    R: Page + 'a,
+    R::Item: 'a,
+    F: Fn(&'a YouTube<HttpsConnector<HttpConnector>>, PageRequest<P>) -> Fut + Copy + 'a,
+    Fut: Future<Output = google_youtube3::Result<(Response<Body>, R)>> + 'a,
+{
+    let first = Some(PageRequest {
+        params,
+        page_token: None,
+    });
+    stream::try_unfold(first, move |request| async move {
+        let request = match request {
+            Some(request) => request,
+            None => return Ok(None),
+        };
+        client
+            .prepare_call(Scope::Readonly, QuotaOperation::List)
+            .await?;
+        let (_res, page) = YoutubeError::api(
+            generic_check_backoff_youtube(
+                &client.client,
+                &request,
+                |client, request: &PageRequest<P>| fetch(client, request.clone()),
+            )
+            .await,
+        )
+        .await?;
+
+        let (items, next_page_token) = page.into_page();
+        let next = next_page_token.map(|page_token| PageRequest {
+            params: request.params.clone(),
+            page_token: Some(page_token),
+        });
+        Ok(Some((
+            stream::iter(items.into_iter().map(Ok::<_, YoutubeError>)),
+            next,
+        )))
+    })
+    .try_flatten()
+}
@@ -0,0 +1,98 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use google_youtube3::api::Playlist;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+
+use crate::prelude::*;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CachedPlaylists {
+    fetched_at: DateTime<Utc>,
+    playlists: Vec<Playlist>,
+}
+
+/// Keeps the playlists of the user around, so looking up the same playlist
+/// over and over does not list all playlists every time.
+///
+/// Optionally the cache is written to disk and survives restarts.
+#[derive(Debug)]
+pub(crate) struct PlaylistCache {
+    ttl: Duration,
+    path: Option<PathBuf>,
+    entry: Mutex<Option<CachedPlaylists>>,
+}
+
+impl PlaylistCache {
+    pub(crate) fn new(ttl: Duration, path: Option<PathBuf>) -> Self {
+        Self {
+            ttl,
+            path,
+            entry: Mutex::new(None),
+        }
+    }
+
+    fn is_fresh(&self, cached: &CachedPlaylists) -> bool {
+        let age = Utc::now().signed_duration_since(cached.fetched_at);
+        age.to_std().map(|age| age < self.ttl).unwrap_or(false)
+    }
+
+    fn load_from_disk(&self) -> Option<CachedPlaylists> {
+        let path = self.path.as_ref()?;
+        let content = std::fs::read_to_string(path).ok()?;
+        serde_json::from_str(&content)
+            .map_err(|e| warn!("could not parse playlist cache {}: {}", path.display(), e))
+            .ok()
+    }
+
+    fn save_to_disk(&self, cached: Option<&CachedPlaylists>) {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return,
+        };
+        let res = match cached {
+            Some(cached) => serde_json::to_string(cached)
+                .map_err(std::io::Error::from)
+                .and_then(|content| std::fs::write(path, content)),
+            None => match std::fs::remove_file(path) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+                _ => Ok(()),
+            },
+        };
+        if let Err(e) = res {
+            warn!("could not update playlist cache {}: {}", path.display(), e);
+        }
+    }
+
+    /// The cached playlists, if they are not older than the ttl.
+    pub(crate) async fn get(&self) -> Option<Vec<Playlist>> {
+        let mut entry = self.entry.lock().await;
+        if entry.is_none() {
+            *entry = self.load_from_disk();
+        }
+        match entry.as_ref() {
+            Some(cached) if self.is_fresh(cached) => {
+                trace!("using {} cached playlists", cached.playlists.len());
+                Some(cached.playlists.clone())
+            }
+            _ => None,
+        }
+    }
+
+    pub(crate) async fn set(&self, playlists: Vec<Playlist>) {
+        let cached = CachedPlaylists {
+            fetched_at: Utc::now(),
+            playlists,
+        };
+        self.save_to_disk(Some(&cached));
+        *self.entry.lock().await = Some(cached);
+    }
+
+    pub(crate) async fn invalidate(&self) {
+        trace!("invalidating playlist cache");
+        self.save_to_disk(None);
+        *self.entry.lock().await = None;
+    }
+}
@@ -0,0 +1,484 @@
+use exponential_backoff::youtube::generic_check_backoff_youtube;
+use futures::{pin_mut, Stream, TryStreamExt};
+use google_youtube3::{
+    api::{
+        Playlist, PlaylistItem, PlaylistItemListResponse, PlaylistItemSnippet,
+        PlaylistListResponse, Video,
+    },
+    hyper::{client::HttpConnector, Body, Response},
+    hyper_rustls::HttpsConnector,
+    YouTube,
+};
+#[cfg(feature = "tracing")]
+use tracing::instrument;
+
+use crate::channel::{ContentOwner, OwnedRequest};
+use crate::pagination::{paginate, PageRequest, MAX_RESULTS};
+use crate::quota::QuotaOperation;
+use crate::scopes::Scope;
+use crate::{PrivacyStatus, YoutubeClient, YoutubeError};
+
+/// A partial update of a playlist, only the fields that are set are changed.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PlaylistPatch {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub privacy_status: Option<PrivacyStatus>,
+}
+
+impl PlaylistPatch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn title(mut self, title: impl Into<String>) -> Self {
+        self.title = Some(title.into());
+        self
+    }
+
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    pub fn privacy(mut self, privacy_status: PrivacyStatus) -> Self {
+        self.privacy_status = Some(privacy_status);
+        self
+    }
+}
+
+/// Anything that identifies a youtube resource: the id itself or an object that carries it.
+pub trait AsId {
+    /// The id, `None` for objects that were never saved.
+    fn as_id(&self) -> Option<&str>;
+}
+
+impl<T: AsId + ?Sized> AsId for &T {
+    fn as_id(&self) -> Option<&str> {
+        (**self).as_id()
+    }
+}
+
+impl AsId for str {
+    fn as_id(&self) -> Option<&str> {
+        Some(self)
+    }
+}
+
+impl AsId for String {
+    fn as_id(&self) -> Option<&str> {
+        Some(self)
+    }
+}
+
+impl AsId for Video {
+    fn as_id(&self) -> Option<&str> {
+        self.id.as_deref()
+    }
+}
+
+impl AsId for Playlist {
+    fn as_id(&self) -> Option<&str> {
+        self.id.as_deref()
+    }
+}
+
+impl AsId for PlaylistItem {
+    fn as_id(&self) -> Option<&str> {
+        self.id.as_deref()
+    }
+}
+
+/// How the title of a playlist is matched when looking it up.
+#[derive(Debug, Clone)]
+pub enum TitleMatcher {
+    Exact(String),
+    IgnoreCase(String),
+    /// Build it with [`TitleMatcher::regex`] to not need `regex` as a dependency.
+    Regex(regex::Regex),
+}
+
+impl TitleMatcher {
+    /// Matches titles against the regular expression, without having to depend on `regex`.
+    pub fn regex(pattern: &str) -> Result<Self, YoutubeError> {
+        regex::Regex::new(pattern)
+            .map(TitleMatcher::Regex)
+            .map_err(YoutubeError::InvalidPattern)
+    }
+
+    pub fn matches(&self, title: &str) -> bool {
+        match self {
+            TitleMatcher::Exact(name) => title == name,
+            TitleMatcher::IgnoreCase(name) => title.to_lowercase() == name.to_lowercase(),
+            TitleMatcher::Regex(regex) => regex.is_match(title),
+        }
+    }
+
+    fn matches_playlist(&self, playlist: &Playlist) -> bool {
+        playlist
+            .snippet
+            .as_ref()
+            .and_then(|snippet| snippet.title.as_deref())
+            .map(|title| self.matches(title))
+            .unwrap_or(false)
+    }
+}
+
+/// Options for [`YoutubeClient::add_video_to_playlist_with`].
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct AddToPlaylistOptions {
+    /// Don't add the video again if it already is in the playlist.
+    pub skip_if_present: bool,
+    /// The zero based position the video is inserted at, appended to the end if not set.
+    ///
+    /// Only works for playlists that are sorted manually.
+    pub position: Option<u32>,
+}
+
+impl AddToPlaylistOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn skip_if_present(mut self, skip: bool) -> Self {
+        self.skip_if_present = skip;
+        self
+    }
+
+    pub fn position(mut self, position: u32) -> Self {
+        self.position = Some(position);
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+struct PlaylistItemParams {
+    part: Vec<String>,
+    playlist_id: String,
+    video_id: Option<String>,
+    content_owner: Option<ContentOwner>,
+}
+
+async fn list_playlist_items(
+    client: &YouTube<HttpsConnector<HttpConnector>>,
+    request: PageRequest<PlaylistItemParams>,
+) -> google_youtube3::Result<(Response<Body>, PlaylistItemListResponse)> {
+    let mut call = client
+        .playlist_items()
+        .list(&request.params.part)
+        .playlist_id(&request.params.playlist_id)
+        .max_results(MAX_RESULTS);
+    if let Some(video_id) = &request.params.video_id {
+        call = call.video_id(video_id);
+    }
+    if let Some(owner) = &request.params.content_owner {
+        call = call.on_behalf_of_content_owner(&owner.owner);
+    }
+    if let Some(page_token) = &request.page_token {
+        call = call.page_token(page_token);
+    }
+    call.doit().await
+}
+
+impl YoutubeClient {
+    /// Lists all items of the playlist in playlist order, walking all result pages.
+    pub fn list_playlist_items(
+        &self,
+        playlist_id: &str,
+    ) -> impl Stream<Item = Result<PlaylistItem, YoutubeError>> + '_ {
+        let para = PlaylistItemParams {
+            part: vec!["snippet".to_string(), "contentDetails".to_string()],
+            playlist_id: playlist_id.to_string(),
+            video_id: None,
+            content_owner: self.content_owner.clone(),
+        };
+        paginate(self, para, list_playlist_items)
+    }
+
+    /// The playlist item of the video, if the video is in the playlist.
+    #[cfg_attr(feature = "tracing", instrument)]
+    pub async fn find_playlist_item(
+        &self,
+        playlist_id: &str,
+        video_id: &str,
+    ) -> Result<Option<PlaylistItem>, YoutubeError> {
+        let params = PageRequest {
+            params: PlaylistItemParams {
+                part: vec!["snippet".to_string()],
+                playlist_id: playlist_id.to_string(),
+                video_id: Some(video_id.to_string()),
+                content_owner: self.content_owner.clone(),
+            },
+            page_token: None,
+        };
+        self.prepare_call(Scope::Readonly, QuotaOperation::List)
+            .await?;
+        let (_res, items) = YoutubeError::api(
+            generic_check_backoff_youtube(&self.client, &params, |client, params| {
+                list_playlist_items(client, params.clone())
+            })
+            .await,
+        )
+        .await?;
+        Ok(items.items.and_then(|items| items.into_iter().next()))
+    }
+
+    #[cfg_attr(feature = "tracing", instrument)]
+    pub async fn is_video_in_playlist(
+        &self,
+        playlist_id: &str,
+        video_id: &str,
+    ) -> Result<bool, YoutubeError> {
+        Ok(self
+            .find_playlist_item(playlist_id, video_id)
+            .await?
+            .is_some())
+    }
+
+    #[cfg_attr(feature = "tracing", instrument)]
+    pub async fn remove_playlist_item(&self, playlist_item_id: &str) -> Result<(), YoutubeError> {
+        async fn delete_playlist_item(
+            client: &YouTube<HttpsConnector<HttpConnector>>,
+            request: &OwnedRequest<String>,
+        ) -> google_youtube3::Result<Response<Body>> {
+            let mut call = client.playlist_items().delete(&request.body);
+            if let Some(owner) = &request.content_owner {
+                call = call.on_behalf_of_content_owner(&owner.owner);
+            }
+            call.doit().await
+        }
+        let params = self.owned_request(playlist_item_id.to_string());
+        self.prepare_call(Scope::Full, QuotaOperation::Delete)
+            .await?;
+        let res = YoutubeError::api(
+            generic_check_backoff_youtube(&self.client, &params, delete_playlist_item).await,
+        )
+        .await?;
+        if res.status().is_success() {
+            Ok(())
+        } else {
+            Err(YoutubeError::status(res).await)
+        }
+    }
+
+    /// Moves the item to the given (zero based) position in its playlist.
+    #[cfg_attr(feature = "tracing", instrument)]
+    pub async fn move_playlist_item(
+        &self,
+        item: &PlaylistItem,
+        position: u32,
+    ) -> Result<PlaylistItem, YoutubeError> {
+        let id = item
+            .id
+            .clone()
+            .ok_or(YoutubeError::MissingId("playlist item"))?;
+        let snippet = item
+            .snippet
+            .as_ref()
+            .ok_or(YoutubeError::MissingId("playlist item snippet"))?;
+        // playlist id and resource id are required, everything else in the snippet is read only
+        let item = PlaylistItem {
+            id: Some(id),
+            snippet: Some(PlaylistItemSnippet {
+                playlist_id: snippet.playlist_id.clone(),
+                resource_id: snippet.resource_id.clone(),
+                position: Some(position),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        async fn update_playlist_item(
+            client: &YouTube<HttpsConnector<HttpConnector>>,
+            request: &OwnedRequest<PlaylistItem>,
+        ) -> google_youtube3::Result<(Response<Body>, PlaylistItem)> {
+            let mut call = client.playlist_items().update(request.body.clone());
+            if let Some(owner) = &request.content_owner {
+                call = call.on_behalf_of_content_owner(&owner.owner);
+            }
+            call.doit().await
+        }
+        let request = self.owned_request(item);
+        self.prepare_call(Scope::Full, QuotaOperation::Update)
+            .await?;
+        let (res, item) = YoutubeError::api(
+            generic_check_backoff_youtube(&self.client, &request, update_playlist_item).await,
+        )
+        .await?;
+        if res.status().is_success() {
+            Ok(item)
+        } else {
+            Err(YoutubeError::status(res).await)
+        }
+    }
+
+    /// All playlists of the user, served from the playlist cache if one is configured.
+    #[cfg_attr(feature = "tracing", instrument)]
+    pub async fn my_playlists(&self) -> Result<Vec<Playlist>, YoutubeError> {
+        if let Some(cache) = &self.playlist_cache {
+            if let Some(playlists) = cache.get().await {
+                return Ok(playlists);
+            }
+        }
+        let playlists: Vec<Playlist> = self.list_my_playlists().try_collect().await?;
+        if let Some(cache) = &self.playlist_cache {
+            cache.set(playlists.clone()).await;
+        }
+        Ok(playlists)
+    }
+
+    /// The first playlist of the user whose title matches.
+    #[cfg_attr(feature = "tracing", instrument)]
+    pub async fn find_playlist(
+        &self,
+        matcher: &TitleMatcher,
+    ) -> Result<Option<Playlist>, YoutubeError> {
+        if self.playlist_cache.is_some() {
+            let playlists = self.my_playlists().await?;
+            return Ok(playlists
+                .into_iter()
+                .find(|playlist| matcher.matches_playlist(playlist)));
+        }
+
+        // without a cache there is no need to list more pages than necessary
+        let playlists = self.list_my_playlists();
+        pin_mut!(playlists);
+        while let Some(playlist) = playlists.try_next().await? {
+            if matcher.matches_playlist(&playlist) {
+                return Ok(Some(playlist));
+            }
+        }
+        Ok(None)
+    }
+
+    pub(crate) async fn invalidate_playlist_cache(&self) {
+        if let Some(cache) = &self.playlist_cache {
+            cache.invalidate().await;
+        }
+    }
+
+    /// Fetches a single playlist by its id.
+    #[cfg_attr(feature = "tracing", instrument)]
+    pub async fn get_playlist(&self, playlist_id: &str) -> Result<Playlist, YoutubeError> {
+        if let Some(cache) = &self.playlist_cache {
+            let cached = cache.get().await.and_then(|playlists| {
+                playlists
+                    .into_iter()
+                    .find(|playlist| playlist.id.as_deref() == Some(playlist_id))
+            });
+            if let Some(playlist) = cached {
+                return Ok(playlist);
+            }
+        }
+
+        struct PlaylistParams {
+            part: Vec<String>,
+            id: String,
+            content_owner: Option<ContentOwner>,
+        }
+        async fn get_playlist(
+            client: &YouTube<HttpsConnector<HttpConnector>>,
+            params: &PlaylistParams,
+        ) -> google_youtube3::Result<(Response<Body>, PlaylistListResponse)> {
+            let mut call = client.playlists().list(&params.part).add_id(&params.id);
+            if let Some(owner) = &params.content_owner {
+                call = call.on_behalf_of_content_owner(&owner.owner);
+            }
+            call.doit().await
+        }
+        let params = PlaylistParams {
+            part: vec!["snippet".to_string(), "status".to_string()],
+            id: playlist_id.to_string(),
+            content_owner: self.content_owner.clone(),
+        };
+        self.prepare_call(Scope::Readonly, QuotaOperation::List)
+            .await?;
+        let (_res, playlists) = YoutubeError::api(
+            generic_check_backoff_youtube(&self.client, &params, get_playlist).await,
+        )
+        .await?;
+        playlists
+            .items
+            .and_then(|items| items.into_iter().next())
+            .ok_or_else(|| YoutubeError::PlaylistNotFound(playlist_id.to_string()))
+    }
+
+    /// Changes only the fields that are set in the patch, the rest of the
+    /// snippet and status is kept as is.
+    #[cfg_attr(feature = "tracing", instrument)]
+    pub async fn update_playlist(
+        &self,
+        playlist_id: &str,
+        patch: &PlaylistPatch,
+    ) -> Result<Playlist, YoutubeError> {
+        let current = self.get_playlist(playlist_id).await?;
+        let mut snippet = current.snippet.unwrap_or_default();
+        if let Some(title) = &patch.title {
+            snippet.title = Some(title.clone());
+        }
+        if let Some(description) = &patch.description {
+            snippet.description = Some(description.clone());
+        }
+        let mut status = current.status.unwrap_or_default();
+        if let Some(privacy) = &patch.privacy_status {
+            status.privacy_status = Some(privacy.to_string());
+        }
+        let playlist = Playlist {
+            id: Some(playlist_id.to_string()),
+            snippet: Some(snippet),
+            status: Some(status),
+            ..Default::default()
+        };
+
+        async fn update_playlist(
+            client: &YouTube<HttpsConnector<HttpConnector>>,
+            request: &OwnedRequest<Playlist>,
+        ) -> google_youtube3::Result<(Response<Body>, Playlist)> {
+            let mut call = client.playlists().update(request.body.clone());
+            if let Some(owner) = &request.content_owner {
+                call = call.on_behalf_of_content_owner(&owner.owner);
+            }
+            call.doit().await
+        }
+        let request = self.owned_request(playlist);
+        self.prepare_call(Scope::Full, QuotaOperation::Update)
+            .await?;
+        let (res, playlist) = YoutubeError::api(
+            generic_check_backoff_youtube(&self.client, &request, update_playlist).await,
+        )
+        .await?;
+        self.invalidate_playlist_cache().await;
+        if res.status().is_success() {
+            Ok(playlist)
+        } else {
+            Err(YoutubeError::status(res).await)
+        }
+    }
+
+    #[cfg_attr(feature = "tracing", instrument)]
+    pub async fn delete_playlist(&self, playlist_id: &str) -> Result<(), YoutubeError> {
+        async fn delete_playlist(
+            client: &YouTube<HttpsConnector<HttpConnector>>,
+            request: &OwnedRequest<String>,
+        ) -> google_youtube3::Result<Response<Body>> {
+            let mut call = client.playlists().delete(&request.body);
+            if let Some(owner) = &request.content_owner {
+                call = call.on_behalf_of_content_owner(&owner.owner);
+            }
+            call.doit().await
+        }
+        let params = self.owned_request(playlist_id.to_string());
+        self.prepare_call(Scope::Full, QuotaOperation::Delete)
+            .await?;
+        let res = YoutubeError::api(
+            generic_check_backoff_youtube(&self.client, &params, delete_playlist).await,
+        )
+        .await?;
+        self.invalidate_playlist_cache().await;
+        if res.status().is_success() {
+            Ok(())
+        } else {
+            Err(YoutubeError::status(res).await)
+        }
+    }
+}
@@ -0,0 +1,187 @@
+use std::collections::HashMap;
+use std::fmt::{Debug, Formatter};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use google_youtube3::{
+    api::Video,
+    hyper::{self, client::HttpConnector},
+    hyper_rustls::HttpsConnector,
+};
+use tokio::sync::{Mutex, OnceCell};
+#[cfg(feature = "tracing")]
+use tracing::instrument;
+
+use crate::prelude::*;
+use crate::quota::QuotaOperation;
+use crate::token::revoke_tokens;
+use crate::{VideoMetadata, YoutubeClient, YoutubeClientBuilder, YoutubeError};
+
+type BuilderFactory = Box<dyn Fn(&str) -> YoutubeClientBuilder + Send + Sync>;
+
+/// Authenticated clients for multiple users, built on first use.
+///
+/// All clients share one hyper client, so they also share its connection pool.
+/// Uploads can be spread over the users, once one of them runs out of quota
+/// the next one is used.
+pub struct YoutubeClientPool {
+    hyper_client: hyper::Client<HttpsConnector<HttpConnector>>,
+    factory: BuilderFactory,
+    users: Vec<String>,
+    current: AtomicUsize,
+    // one cell per user, so building a client does not block the other users
+    clients: Mutex<HashMap<String, Arc<OnceCell<Arc<YoutubeClient>>>>>,
+}
+
+impl Debug for YoutubeClientPool {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("YoutubeClientPool")
+            .field("users", &self.users)
+            .field("current", &self.current)
+            .finish()
+    }
+}
+
+impl YoutubeClientPool {
+    /// Creates a pool that gets the builder for each user from the factory.
+    ///
+    /// The user and the hyper client of the returned builders are set by the pool.
+    pub fn new<F>(factory: F) -> Self
+    where
+        F: Fn(&str) -> YoutubeClientBuilder + Send + Sync + 'static,
+    {
+        let hyper_client = YoutubeClientBuilder::new().build_hyper_client();
+        Self::with_hyper_client(hyper_client, factory)
+    }
+
+    pub fn with_hyper_client<F>(
+        hyper_client: hyper::Client<HttpsConnector<HttpConnector>>,
+        factory: F,
+    ) -> Self
+    where
+        F: Fn(&str) -> YoutubeClientBuilder + Send + Sync + 'static,
+    {
+        Self {
+            hyper_client,
+            factory: Box::new(factory),
+            users: Vec::new(),
+            current: AtomicUsize::new(0),
+            clients: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Adds a user to the rotation used by [`Self::upload_video_with`] and [`Self::client_for`].
+    pub fn add_user(mut self, user: impl Into<String>) -> Self {
+        self.users.push(user.into());
+        self
+    }
+
+    pub fn users(&self) -> &[String] {
+        &self.users
+    }
+
+    /// The client of the user, it is built and authenticated on the first call.
+    ///
+    /// Only concurrent calls for the same user wait for the build, which can
+    /// include the interactive authorization.
+    #[cfg_attr(feature = "tracing", instrument)]
+    pub async fn get(&self, user: &str) -> Result<Arc<YoutubeClient>, YoutubeError> {
+        let cell = self
+            .clients
+            .lock()
+            .await
+            .entry(user.to_string())
+            .or_default()
+            .clone();
+        cell.get_or_try_init(|| async {
+            debug!("building youtube client for user {}", user);
+            let client = (self.factory)(user)
+                .user(user)
+                .hyper_client(self.hyper_client.clone())
+                .build()
+                .await?;
+            Ok::<_, YoutubeError>(Arc::new(client))
+        })
+        .await
+        .cloned()
+    }
+
+    /// Drops the cached client of the user, the next [`Self::get`] builds a new one.
+    pub async fn remove(&self, user: &str) -> Option<Arc<YoutubeClient>> {
+        let cell = self.clients.lock().await.remove(user)?;
+        cell.get().cloned()
+    }
+
+    /// Logs the user out, see [`YoutubeClient::logout`], and drops the cached client.
+    ///
+    /// A user without a cached client is logged out straight from its token store,
+    /// no client is built for it, which needs the builder of the user to keep its tokens
+    /// in the store. The user stays in the rotation, using it again starts a new authorization.
+    #[cfg_attr(feature = "tracing", instrument)]
+    pub async fn revoke_user(&self, user: &str) -> Result<(), YoutubeError> {
+        if let Some(client) = self.remove(user).await {
+            return client.logout().await;
+        }
+        let mut builder = (self.factory)(user);
+        if !builder.uses_token_store() {
+            return Err(YoutubeError::NoTokenStore);
+        }
+        let config = builder.take_config();
+        let store = builder.take_token_store(&config);
+        revoke_tokens(&self.hyper_client, &store, user, &config.revoke_url).await
+    }
+
+    /// The first user of the rotation, starting at the current one, that can still afford the operation.
+    ///
+    /// The quota of a user is only known once its client is built, so this
+    /// builds the clients of the users it looks at. For a user without a stored
+    /// token that starts the interactive authorization.
+    #[cfg_attr(feature = "tracing", instrument)]
+    pub async fn client_for(
+        &self,
+        operation: QuotaOperation,
+    ) -> Result<(String, Arc<YoutubeClient>), YoutubeError> {
+        let start = self.current.load(Ordering::SeqCst);
+        for offset in 0..self.users.len() {
+            let index = (start + offset) % self.users.len();
+            let user = &self.users[index];
+            let client = self.get(user).await?;
+            if client.quota().can_afford(operation) {
+                if offset > 0 {
+                    info!("switching to user {} for {}", user, operation);
+                    self.current.store(index, Ordering::SeqCst);
+                }
+                return Ok((user.clone(), client));
+            }
+            debug!("user {} has not enough quota left for {}", user, operation);
+        }
+        Err(YoutubeError::PoolExhausted(operation))
+    }
+
+    /// Uploads the video with the current user, moving on to the next one
+    /// if youtube reports that the quota of the user is exceeded.
+    ///
+    /// Returns the user the video was uploaded with alongside the video.
+    #[cfg_attr(feature = "tracing", instrument)]
+    pub async fn upload_video_with(
+        &self,
+        path: &Path,
+        metadata: &VideoMetadata,
+    ) -> Result<(String, Video), YoutubeError> {
+        loop {
+            let (user, client) = self.client_for(QuotaOperation::VideoInsert).await?;
+            match client.upload_video_with(path, metadata).await {
+                Err(YoutubeError::QuotaExceeded(e)) => {
+                    warn!("quota of user {} is exceeded: {}", user, e);
+                    client.quota().mark_exhausted().await;
+                }
+                Err(YoutubeError::QuotaBudgetExceeded { .. }) => {
+                    // another task used up the budget in the meantime
+                    continue;
+                }
+                res => return res.map(|video| (user, video)),
+            }
+        }
+    }
+}
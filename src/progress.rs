@@ -0,0 +1,95 @@
+use std::io::{Read, Seek, SeekFrom};
+use std::time::{Duration, Instant};
+
+use tokio::sync::watch;
+
+/// The progress of a running upload.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct UploadProgress {
+    /// Bytes of the file that have been handed to the upload so far.
+    pub bytes_sent: u64,
+    pub total_bytes: u64,
+    /// Average throughput since the upload started.
+    pub bytes_per_second: f64,
+    /// Estimated time until the upload is done. `None` until there is a throughput to go by.
+    pub eta: Option<Duration>,
+}
+
+impl UploadProgress {
+    pub fn fraction(&self) -> f64 {
+        if self.total_bytes == 0 {
+            return 0.0;
+        }
+        self.bytes_sent as f64 / self.total_bytes as f64
+    }
+}
+
+/// Wraps the file that is passed to the upload and reports every read to a watch channel.
+pub(crate) struct ProgressReader<'a, R> {
+    inner: R,
+    position: u64,
+    start_position: u64,
+    total: u64,
+    started: Instant,
+    progress: &'a watch::Sender<UploadProgress>,
+}
+
+impl<'a, R: Read + Seek> ProgressReader<'a, R> {
+    pub(crate) fn new(
+        mut inner: R,
+        progress: &'a watch::Sender<UploadProgress>,
+    ) -> std::io::Result<Self> {
+        let position = inner.stream_position()?;
+        let total = inner.seek(SeekFrom::End(0))?;
+        inner.seek(SeekFrom::Start(position))?;
+        let reader = Self {
+            inner,
+            position,
+            start_position: position,
+            total,
+            started: Instant::now(),
+            progress,
+        };
+        reader.report();
+        Ok(reader)
+    }
+
+    fn report(&self) {
+        let elapsed = self.started.elapsed().as_secs_f64();
+        let transferred = self.position.saturating_sub(self.start_position);
+        let bytes_per_second = if elapsed > 0.0 {
+            transferred as f64 / elapsed
+        } else {
+            0.0
+        };
+        let eta = if bytes_per_second > 0.0 {
+            let remaining = self.total.saturating_sub(self.position);
+            Some(Duration::from_secs_f64(remaining as f64 / bytes_per_second))
+        } else {
+            None
+        };
+        self.progress.send_replace(UploadProgress {
+            bytes_sent: self.position,
+            total_bytes: self.total,
+            bytes_per_second,
+            eta,
+        });
+    }
+}
+
+impl<'a, R: Read + Seek> Read for ProgressReader<'a, R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.position += n as u64;
+        self.report();
+        Ok(n)
+    }
+}
+
+impl<'a, R: Read + Seek> Seek for ProgressReader<'a, R> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        self.position = self.inner.seek(pos)?;
+        self.report();
+        Ok(self.position)
+    }
+}
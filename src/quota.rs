@@ -0,0 +1,319 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use chrono::{DateTime, Duration, NaiveDate, TimeZone, Utc};
+use chrono_tz::America::Los_Angeles;
+use serde::{Deserialize, Serialize};
+
+use crate::prelude::*;
+use crate::YoutubeError;
+
+/// The quota a project gets per day unless google granted more.
+pub const DEFAULT_DAILY_QUOTA: u64 = 10_000;
+
+/// The kinds of api calls the client makes, grouped by what they cost.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum QuotaOperation {
+    /// Any `list` call, charged per page.
+    List,
+    /// `videos.insert`
+    VideoInsert,
+    /// Any other `insert` call.
+    Insert,
+    Update,
+    Delete,
+    /// `thumbnails.set`
+    ThumbnailSet,
+}
+
+impl QuotaOperation {
+    /// The units youtube charges for a single call.
+    pub fn cost(&self) -> u64 {
+        match self {
+            QuotaOperation::List => 1,
+            QuotaOperation::VideoInsert => 1600,
+            QuotaOperation::Insert
+            | QuotaOperation::Update
+            | QuotaOperation::Delete
+            | QuotaOperation::ThumbnailSet => 50,
+        }
+    }
+}
+
+impl Display for QuotaOperation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            QuotaOperation::List => "list",
+            QuotaOperation::VideoInsert => "videos.insert",
+            QuotaOperation::Insert => "insert",
+            QuotaOperation::Update => "update",
+            QuotaOperation::Delete => "delete",
+            QuotaOperation::ThumbnailSet => "thumbnails.set",
+        };
+        f.write_str(name)
+    }
+}
+
+/// What happens to a call that would go over the budget.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum QuotaPolicy {
+    /// Only keep track of the usage, never hold back a call.
+    #[default]
+    Track,
+    /// Fail with [`YoutubeError::QuotaBudgetExceeded`] without making the call.
+    Refuse,
+    /// Wait until the quota resets and make the call then.
+    Defer,
+}
+
+/// The units used on a single (pacific time) day.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuotaUsage {
+    pub day: NaiveDate,
+    pub used: u64,
+    pub by_operation: HashMap<QuotaOperation, u64>,
+}
+
+impl QuotaUsage {
+    fn new(day: NaiveDate) -> Self {
+        Self {
+            day,
+            used: 0,
+            by_operation: HashMap::new(),
+        }
+    }
+}
+
+/// The day the youtube quota is currently counted for. It resets at midnight pacific time.
+fn quota_day(now: DateTime<Utc>) -> NaiveDate {
+    now.with_timezone(&Los_Angeles).date_naive()
+}
+
+/// The point in time the quota of the given day runs out.
+fn reset_time(day: NaiveDate) -> DateTime<Utc> {
+    let next_day = day.succ_opt().unwrap_or(day);
+    let midnight = next_day
+        .and_hms_opt(0, 0, 0)
+        .expect("midnight is a valid time");
+    Los_Angeles
+        .from_local_datetime(&midnight)
+        .earliest()
+        .map(|time| time.with_timezone(&Utc))
+        .unwrap_or_else(|| Utc::now() + Duration::days(1))
+}
+
+/// Keeps track of the quota units the client used today.
+///
+/// Every call is charged once before it is made. Youtube charges failed
+/// calls as well, so the retries of the exponential backoff are not counted
+/// and the tracked usage is too low while requests are failing. Keep some
+/// headroom in the budget to make up for that.
+///
+/// The usage can be persisted, so restarts during the day do not forget it.
+#[derive(Debug)]
+pub struct QuotaTracker {
+    budget: u64,
+    policy: QuotaPolicy,
+    path: Option<PathBuf>,
+    usage: Mutex<QuotaUsage>,
+    // only one write at a time, each one writes the usage as it is by then
+    save_lock: tokio::sync::Mutex<()>,
+}
+
+impl QuotaTracker {
+    pub(crate) fn new(budget: u64, policy: QuotaPolicy, path: Option<PathBuf>) -> Self {
+        let today = quota_day(Utc::now());
+        let usage = path
+            .as_ref()
+            .and_then(|path| {
+                let content = std::fs::read_to_string(path).ok()?;
+                serde_json::from_str::<QuotaUsage>(&content)
+                    .map_err(|e| warn!("could not parse quota usage {}: {}", path.display(), e))
+                    .ok()
+            })
+            .filter(|usage| usage.day == today)
+            .unwrap_or_else(|| QuotaUsage::new(today));
+        Self {
+            budget,
+            policy,
+            path,
+            usage: Mutex::new(usage),
+            save_lock: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    pub fn budget(&self) -> u64 {
+        self.budget
+    }
+
+    pub fn policy(&self) -> QuotaPolicy {
+        self.policy
+    }
+
+    /// The usage of today.
+    pub fn usage(&self) -> QuotaUsage {
+        let mut usage = self.usage.lock().unwrap();
+        Self::roll_over(&mut usage);
+        usage.clone()
+    }
+
+    pub fn used(&self) -> u64 {
+        self.usage().used
+    }
+
+    /// The units that are left of the budget for today.
+    pub fn remaining(&self) -> u64 {
+        self.budget.saturating_sub(self.used())
+    }
+
+    /// Whether the operation still fits into the budget of today.
+    pub fn can_afford(&self, operation: QuotaOperation) -> bool {
+        operation.cost() <= self.remaining()
+    }
+
+    /// When the quota resets next.
+    pub fn resets_at(&self) -> DateTime<Utc> {
+        reset_time(quota_day(Utc::now()))
+    }
+
+    /// Counts the whole budget as used, for when youtube reports the quota as
+    /// exceeded before the tracker noticed.
+    pub(crate) async fn mark_exhausted(&self) {
+        {
+            let mut usage = self.usage.lock().unwrap();
+            Self::roll_over(&mut usage);
+            usage.used = usage.used.max(self.budget);
+        }
+        self.save().await;
+    }
+
+    fn roll_over(usage: &mut QuotaUsage) {
+        let today = quota_day(Utc::now());
+        if usage.day != today {
+            debug!("quota reset, used {} units on {}", usage.used, usage.day);
+            *usage = QuotaUsage::new(today);
+        }
+    }
+
+    /// Writes the current usage on the blocking thread pool, outside the lock of the usage.
+    async fn save(&self) {
+        let path = match &self.path {
+            Some(path) => path.clone(),
+            None => return,
+        };
+        let _guard = self.save_lock.lock().await;
+        let usage = self.usage.lock().unwrap().clone();
+        let res = tokio::task::spawn_blocking(move || {
+            serde_json::to_string(&usage)
+                .map_err(std::io::Error::from)
+                .and_then(|content| std::fs::write(&path, content))
+                .map_err(|e| (path, e))
+        })
+        .await;
+        match res {
+            Ok(Ok(())) => {}
+            Ok(Err((path, e))) => warn!("could not save quota usage {}: {}", path.display(), e),
+            Err(e) => warn!("could not save quota usage: {}", e),
+        }
+    }
+
+    /// Charges the operation, depending on the policy this fails or waits
+    /// if the budget does not allow it.
+    pub(crate) async fn reserve(&self, operation: QuotaOperation) -> Result<(), YoutubeError> {
+        let cost = operation.cost();
+        loop {
+            let wait_until = {
+                let mut usage = self.usage.lock().unwrap();
+                Self::roll_over(&mut usage);
+                let remaining = self.budget.saturating_sub(usage.used);
+                if cost <= remaining || self.policy == QuotaPolicy::Track {
+                    usage.used += cost;
+                    *usage.by_operation.entry(operation).or_default() += cost;
+                    trace!(
+                        "charged {} units for {}, {} used today",
+                        cost,
+                        operation,
+                        usage.used
+                    );
+                    None
+                } else if self.policy == QuotaPolicy::Refuse {
+                    return Err(YoutubeError::QuotaBudgetExceeded {
+                        operation,
+                        cost,
+                        remaining,
+                    });
+                } else {
+                    Some(reset_time(usage.day))
+                }
+            };
+            let wait_until = match wait_until {
+                Some(wait_until) => wait_until,
+                None => {
+                    self.save().await;
+                    return Ok(());
+                }
+            };
+            let wait = (wait_until - Utc::now())
+                .to_std()
+                .unwrap_or(std::time::Duration::from_secs(1));
+            info!(
+                "quota budget exhausted, deferring {} until {}",
+                operation, wait_until
+            );
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc(time: &str) -> DateTime<Utc> {
+        time.parse().unwrap()
+    }
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn quota_day_changes_at_midnight_in_los_angeles() {
+        // pacific standard time is 8 hours behind
+        assert_eq!(quota_day(utc("2024-01-15T07:59:59Z")), date(2024, 1, 14));
+        assert_eq!(quota_day(utc("2024-01-15T08:00:00Z")), date(2024, 1, 15));
+        // pacific daylight time is 7 hours behind
+        assert_eq!(quota_day(utc("2024-07-15T06:59:59Z")), date(2024, 7, 14));
+        assert_eq!(quota_day(utc("2024-07-15T07:00:00Z")), date(2024, 7, 15));
+    }
+
+    #[test]
+    fn reset_time_is_the_next_midnight_in_los_angeles() {
+        assert_eq!(reset_time(date(2024, 1, 14)), utc("2024-01-15T08:00:00Z"));
+        assert_eq!(reset_time(date(2024, 7, 14)), utc("2024-07-15T07:00:00Z"));
+        let now = utc("2024-01-15T03:00:00Z");
+        assert!(reset_time(quota_day(now)) > now);
+    }
+
+    #[test]
+    fn daylight_saving_days_are_shorter_and_longer() {
+        // the clocks went forward on 2024-03-10 and back on 2024-11-03
+        assert_eq!(reset_time(date(2024, 3, 9)), utc("2024-03-10T08:00:00Z"));
+        assert_eq!(reset_time(date(2024, 3, 10)), utc("2024-03-11T07:00:00Z"));
+        assert_eq!(
+            reset_time(date(2024, 3, 10)) - reset_time(date(2024, 3, 9)),
+            Duration::hours(23)
+        );
+        assert_eq!(reset_time(date(2024, 11, 2)), utc("2024-11-03T07:00:00Z"));
+        assert_eq!(reset_time(date(2024, 11, 3)), utc("2024-11-04T08:00:00Z"));
+        assert_eq!(
+            reset_time(date(2024, 11, 3)) - reset_time(date(2024, 11, 2)),
+            Duration::hours(25)
+        );
+        // the repeated hour still belongs to the same day
+        assert_eq!(quota_day(utc("2024-11-03T08:30:00Z")), date(2024, 11, 3));
+        assert_eq!(quota_day(utc("2024-11-04T07:59:59Z")), date(2024, 11, 3));
+    }
+}
@@ -0,0 +1,320 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use futures::stream::{FuturesUnordered, StreamExt};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::prelude::*;
+
+const DEFAULT_SUCCESS_PAGE: &str = "<!DOCTYPE html><html><head><title>Authenticated</title></head>\
+<body><h1>Authentication successful</h1><p>You can close this window now.</p></body></html>";
+
+/// The largest request head that is read before giving up on a connection.
+const MAX_REQUEST_BYTES: usize = 16 * 1024;
+/// How long a connection may take to send its request. Browsers open
+/// speculative connections that never send anything.
+const CONNECTION_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A small http listener on localhost that receives the oauth redirect and
+/// takes the `code` out of it, so no code has to be copied around by hand.
+///
+/// The listener only lives until the code arrived.
+#[derive(Debug, Clone)]
+pub struct LocalRedirectServer {
+    port: u16,
+    path: String,
+    redirect_uri: String,
+    success_page: String,
+    timeout: Option<Duration>,
+}
+
+impl Default for LocalRedirectServer {
+    fn default() -> Self {
+        Self::new(8080)
+    }
+}
+
+impl LocalRedirectServer {
+    pub fn new(port: u16) -> Self {
+        let path = "/googleapi/auth".to_string();
+        Self {
+            port,
+            redirect_uri: format!("http://localhost:{}{}", port, path),
+            path,
+            success_page: DEFAULT_SUCCESS_PAGE.to_string(),
+            timeout: None,
+        }
+    }
+
+    /// The path the redirect is expected at, defaults to `/googleapi/auth`.
+    pub fn path(mut self, path: impl Into<String>) -> Self {
+        let mut path = path.into();
+        if !path.starts_with('/') {
+            path.insert(0, '/');
+        }
+        self.redirect_uri = format!("http://localhost:{}{}", self.port, path);
+        self.path = path;
+        self
+    }
+
+    /// The html that is shown in the browser once the code was received.
+    pub fn success_page(mut self, html: impl Into<String>) -> Self {
+        self.success_page = html.into();
+        self
+    }
+
+    /// Give up if no code arrived in this time.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// The uri that has to be registered as redirect uri of the application.
+    pub fn redirect_uri(&self) -> &str {
+        &self.redirect_uri
+    }
+
+    /// Listens until a request with a code arrives on the redirect path and returns the code.
+    pub async fn wait_for_code(&self) -> std::io::Result<String> {
+        match self.timeout {
+            Some(timeout) => tokio::time::timeout(timeout, self.listen())
+                .await
+                .map_err(|_| {
+                    std::io::Error::new(
+                        std::io::ErrorKind::TimedOut,
+                        "no auth code was received in time",
+                    )
+                })?,
+            None => self.listen().await,
+        }
+    }
+
+    async fn listen(&self) -> std::io::Result<String> {
+        let listener = TcpListener::bind(("127.0.0.1", self.port)).await?;
+        info!("waiting for the auth redirect on {}", self.redirect_uri);
+        // connections are handled side by side, an idle one must not hold up the redirect
+        let mut connections = FuturesUnordered::new();
+        loop {
+            tokio::select! {
+                accepted = listener.accept() => {
+                    let (stream, peer) = accepted?;
+                    trace!("auth redirect connection from {}", peer);
+                    connections.push(self.handle_connection(stream, peer));
+                }
+                Some(handled) = connections.next() => match handled {
+                    Ok(Some(code)) => return Ok(code),
+                    Ok(None) => continue,
+                    Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => return Err(e),
+                    Err(e) => {
+                        warn!("error while handling the auth redirect: {}", e);
+                        continue;
+                    }
+                },
+            }
+        }
+    }
+
+    /// Handles the connection, giving up on it after [`CONNECTION_TIMEOUT`].
+    async fn handle_connection(
+        &self,
+        mut stream: TcpStream,
+        peer: SocketAddr,
+    ) -> std::io::Result<Option<String>> {
+        match tokio::time::timeout(CONNECTION_TIMEOUT, self.handle(&mut stream)).await {
+            Ok(handled) => handled,
+            Err(_) => {
+                trace!("connection from {} sent no request in time", peer);
+                Ok(None)
+            }
+        }
+    }
+
+    /// Answers a single request, returns the code if the request carried one.
+    async fn handle(&self, stream: &mut TcpStream) -> std::io::Result<Option<String>> {
+        let target = match read_request_target(stream).await? {
+            Some(target) => target,
+            None => {
+                respond(stream, "400 Bad Request", "bad request").await?;
+                return Ok(None);
+            }
+        };
+        let (path, query) = target.split_once('?').unwrap_or((&target, ""));
+        if path != self.path {
+            respond(stream, "404 Not Found", "not found").await?;
+            return Ok(None);
+        }
+        let params = parse_query(query);
+        if let Some(error) = params.get("error") {
+            respond(
+                stream,
+                "200 OK",
+                &format!("Authentication failed: {}", html_escape(error)),
+            )
+            .await?;
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::PermissionDenied,
+                format!("the authorization was denied: {}", error),
+            ));
+        }
+        match params.get("code") {
+            Some(code) if !code.is_empty() => {
+                respond(stream, "200 OK", &self.success_page).await?;
+                Ok(Some(code.clone()))
+            }
+            _ => {
+                respond(stream, "400 Bad Request", "the request contains no code").await?;
+                Ok(None)
+            }
+        }
+    }
+}
+
+/// Reads the request head and returns the target of the request line.
+async fn read_request_target(stream: &mut TcpStream) -> std::io::Result<Option<String>> {
+    let mut request = Vec::new();
+    let mut buf = [0u8; 1024];
+    while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+        if request.len() > MAX_REQUEST_BYTES {
+            return Ok(None);
+        }
+        let read = stream.read(&mut buf).await?;
+        if read == 0 {
+            break;
+        }
+        request.extend_from_slice(&buf[..read]);
+    }
+    let request = String::from_utf8_lossy(&request);
+    let mut request_line = request.lines().next().unwrap_or_default().split(' ');
+    match (request_line.next(), request_line.next()) {
+        (Some("GET"), Some(target)) => Ok(Some(target.to_string())),
+        _ => Ok(None),
+    }
+}
+
+async fn respond(stream: &mut TcpStream, status: &str, body: &str) -> std::io::Result<()> {
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+fn parse_query(query: &str) -> HashMap<String, String> {
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (percent_decode(key), percent_decode(value))
+        })
+        .collect()
+}
+
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => decoded.push(b' '),
+            b'%' if i + 2 < bytes.len()
+                && bytes[i + 1].is_ascii_hexdigit()
+                && bytes[i + 2].is_ascii_hexdigit() =>
+            {
+                let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).unwrap_or_default();
+                decoded.push(u8::from_str_radix(hex, 16).unwrap_or_default());
+                i += 2;
+            }
+            byte => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn html_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percent_decode_handles_escapes_and_plus() {
+        assert_eq!(percent_decode("4%2F0AfJ"), "4/0AfJ");
+        assert_eq!(percent_decode("a+b%20c"), "a b c");
+        assert_eq!(percent_decode("%C3%BCber"), "über");
+        assert_eq!(percent_decode("%2b"), "+");
+    }
+
+    #[test]
+    fn percent_decode_keeps_incomplete_escapes() {
+        assert_eq!(percent_decode("%"), "%");
+        assert_eq!(percent_decode("a%"), "a%");
+        assert_eq!(percent_decode("a%4"), "a%4");
+        assert_eq!(percent_decode("%41"), "A");
+        assert_eq!(percent_decode("a%41"), "aA");
+        assert_eq!(percent_decode("%zz"), "%zz");
+        // from_str_radix would take the sign, the plus is a space instead
+        assert_eq!(percent_decode("%+1"), "% 1");
+        assert_eq!(percent_decode("%%41"), "%A");
+    }
+
+    #[test]
+    fn parse_query_splits_pairs() {
+        let params = parse_query("code=4%2F0Af&scope=a+b&empty=&flag&&");
+        assert_eq!(params.get("code").map(String::as_str), Some("4/0Af"));
+        assert_eq!(params.get("scope").map(String::as_str), Some("a b"));
+        assert_eq!(params.get("empty").map(String::as_str), Some(""));
+        assert_eq!(params.get("flag").map(String::as_str), Some(""));
+        assert_eq!(params.len(), 4);
+        assert!(parse_query("").is_empty());
+    }
+
+    #[test]
+    fn parse_query_keeps_equals_in_values() {
+        let params = parse_query("state=a=b");
+        assert_eq!(params.get("state").map(String::as_str), Some("a=b"));
+    }
+
+    #[tokio::test]
+    async fn idle_connection_does_not_block_the_redirect() {
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let server = LocalRedirectServer::new(port).timeout(Duration::from_secs(30));
+        let waiting = tokio::spawn(async move { server.wait_for_code().await });
+
+        // wait until the server listens
+        let idle = loop {
+            match TcpStream::connect(("127.0.0.1", port)).await {
+                Ok(stream) => break stream,
+                Err(_) => tokio::time::sleep(Duration::from_millis(10)).await,
+            }
+        };
+        let mut redirect = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+        redirect
+            .write_all(b"GET /googleapi/auth?code=4%2F0Af HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .await
+            .unwrap();
+
+        let code = tokio::time::timeout(CONNECTION_TIMEOUT / 5, waiting)
+            .await
+            .expect("the idle connection held up the redirect")
+            .unwrap()
+            .unwrap();
+        assert_eq!(code, "4/0Af");
+        drop(idle);
+    }
+}
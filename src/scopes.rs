@@ -1,3 +1,164 @@
+use std::collections::BTreeSet;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use crate::YoutubeError;
+
 pub const YOUTUBE_READONLY: &str = "https://www.googleapis.com/auth/youtube.readonly";
 pub const YOUTUBE: &str = "https://www.googleapis.com/auth/youtube";
-pub const YOUTUBE_UPLOAD: &str = "https://www.googleapis.com/auth/youtube.upload";
\ No newline at end of file
+pub const YOUTUBE_UPLOAD: &str = "https://www.googleapis.com/auth/youtube.upload";
+pub const YOUTUBE_FORCE_SSL: &str = "https://www.googleapis.com/auth/youtube.force-ssl";
+pub const YOUTUBE_PARTNER: &str = "https://www.googleapis.com/auth/youtubepartner";
+pub const YOUTUBE_CHANNEL_MEMBERSHIPS: &str =
+    "https://www.googleapis.com/auth/youtube.channel-memberships.creator";
+
+/// The oauth scopes of the youtube data api.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Scope {
+    Readonly,
+    Upload,
+    /// Manage the account, allows everything [`Scope::Readonly`] and [`Scope::Upload`] allow.
+    Full,
+    /// Same as [`Scope::Full`], but only over ssl.
+    ForceSsl,
+    Partner,
+    ChannelMemberships,
+}
+
+impl Scope {
+    pub const ALL: [Scope; 6] = [
+        Scope::Readonly,
+        Scope::Upload,
+        Scope::Full,
+        Scope::ForceSsl,
+        Scope::Partner,
+        Scope::ChannelMemberships,
+    ];
+
+    pub fn url(&self) -> &'static str {
+        match self {
+            Scope::Readonly => YOUTUBE_READONLY,
+            Scope::Upload => YOUTUBE_UPLOAD,
+            Scope::Full => YOUTUBE,
+            Scope::ForceSsl => YOUTUBE_FORCE_SSL,
+            Scope::Partner => YOUTUBE_PARTNER,
+            Scope::ChannelMemberships => YOUTUBE_CHANNEL_MEMBERSHIPS,
+        }
+    }
+
+    /// Whether a token granted for this scope can be used for calls that need `other`.
+    pub fn covers(&self, other: Scope) -> bool {
+        match self {
+            Scope::Full | Scope::ForceSsl => matches!(
+                other,
+                Scope::Readonly | Scope::Upload | Scope::Full | Scope::ForceSsl
+            ),
+            scope => *scope == other,
+        }
+    }
+}
+
+impl AsRef<str> for Scope {
+    fn as_ref(&self) -> &str {
+        self.url()
+    }
+}
+
+impl Display for Scope {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.url())
+    }
+}
+
+impl FromStr for Scope {
+    type Err = YoutubeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Scope::ALL
+            .into_iter()
+            .find(|scope| scope.url() == s)
+            .ok_or_else(|| YoutubeError::UnknownScope(s.to_string()))
+    }
+}
+
+/// The scopes a client was authorized for.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ScopeSet {
+    scopes: BTreeSet<Scope>,
+}
+
+impl ScopeSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with(mut self, scope: Scope) -> Self {
+        self.insert(scope);
+        self
+    }
+
+    pub fn insert(&mut self, scope: Scope) -> bool {
+        self.scopes.insert(scope)
+    }
+
+    pub fn contains(&self, scope: Scope) -> bool {
+        self.scopes.contains(&scope)
+    }
+
+    /// Whether any scope of the set covers `scope`, see [`Scope::covers`].
+    pub fn allows(&self, scope: Scope) -> bool {
+        self.scopes.iter().any(|granted| granted.covers(scope))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.scopes.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Scope> + '_ {
+        self.scopes.iter().copied()
+    }
+
+    pub fn urls(&self) -> Vec<&'static str> {
+        self.iter().map(|scope| scope.url()).collect()
+    }
+
+    /// Parses scope urls, anything that looks like a youtube scope but is not
+    /// known fails. Other scopes (like `email`) are returned separately.
+    pub fn parse<S: AsRef<str>>(scopes: &[S]) -> Result<(Self, Vec<String>), YoutubeError> {
+        let mut set = Self::new();
+        let mut others = Vec::new();
+        for scope in scopes {
+            let scope = scope.as_ref();
+            match scope.parse() {
+                Ok(scope) => {
+                    set.insert(scope);
+                }
+                Err(e) if scope.contains("youtube") => return Err(e),
+                Err(_) => others.push(scope.to_string()),
+            }
+        }
+        Ok((set, others))
+    }
+}
+
+impl FromIterator<Scope> for ScopeSet {
+    fn from_iter<T: IntoIterator<Item = Scope>>(iter: T) -> Self {
+        Self {
+            scopes: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl From<Scope> for ScopeSet {
+    fn from(scope: Scope) -> Self {
+        Self::new().with(scope)
+    }
+}
+
+/// Whether a token granted for the `granted` scope url works for `requested`.
+pub(crate) fn url_covers(granted: &str, requested: &str) -> bool {
+    match (granted.parse::<Scope>(), requested.parse::<Scope>()) {
+        (Ok(granted), Ok(requested)) => granted.covers(requested),
+        _ => granted == requested,
+    }
+}
@@ -0,0 +1,257 @@
+use std::fmt::Debug;
+use std::io::Cursor;
+use std::path::Path;
+
+use exponential_backoff::youtube::generic_check_backoff_youtube;
+use google_youtube3::{
+    api::ThumbnailSetResponse,
+    hyper::{client::HttpConnector, Body, Response},
+    hyper_rustls::HttpsConnector,
+    YouTube,
+};
+#[cfg(feature = "tracing")]
+use tracing::instrument;
+
+use crate::channel::ContentOwner;
+use crate::prelude::*;
+use crate::quota::QuotaOperation;
+use crate::scopes::Scope;
+use crate::{YoutubeClient, YoutubeError};
+
+/// Youtube rejects thumbnails larger than this.
+pub const MAX_THUMBNAIL_BYTES: u64 = 2 * 1024 * 1024;
+pub const RECOMMENDED_THUMBNAIL_SIZE: (u32, u32) = (1280, 720);
+
+/// Why a thumbnail file can't be used.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ThumbnailError {
+    #[error("the thumbnail is {size} bytes large, the maximum is {max}")]
+    TooLarge { size: u64, max: u64 },
+    #[error("the thumbnail is neither a JPEG nor a PNG")]
+    UnsupportedFormat,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ThumbnailFormat {
+    Jpeg,
+    Png,
+}
+
+impl ThumbnailFormat {
+    /// Detects the format from the magic bytes at the start of the file.
+    pub fn detect(data: &[u8]) -> Option<Self> {
+        if data.starts_with(&[0xFF, 0xD8, 0xFF]) {
+            Some(ThumbnailFormat::Jpeg)
+        } else if data.starts_with(&[0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A]) {
+            Some(ThumbnailFormat::Png)
+        } else {
+            None
+        }
+    }
+
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            ThumbnailFormat::Jpeg => "image/jpeg",
+            ThumbnailFormat::Png => "image/png",
+        }
+    }
+
+    /// Reads the width and height from the image header.
+    pub fn dimensions(&self, data: &[u8]) -> Option<(u32, u32)> {
+        match self {
+            ThumbnailFormat::Png => {
+                // the IHDR chunk always comes first, right after the signature
+                let width = data.get(16..20)?;
+                let height = data.get(20..24)?;
+                Some((
+                    u32::from_be_bytes(width.try_into().ok()?),
+                    u32::from_be_bytes(height.try_into().ok()?),
+                ))
+            }
+            ThumbnailFormat::Jpeg => jpeg_dimensions(data),
+        }
+    }
+}
+
+/// Walks the jpeg segments until the start of frame that holds the dimensions.
+fn jpeg_dimensions(data: &[u8]) -> Option<(u32, u32)> {
+    let mut i = 2;
+    while i + 1 < data.len() {
+        if data[i] != 0xFF {
+            return None;
+        }
+        let marker = data[i + 1];
+        match marker {
+            // fill byte in front of a marker
+            0xFF => {
+                i += 1;
+                continue;
+            }
+            // standalone markers have no length
+            0x01 | 0xD0..=0xD7 => {
+                i += 2;
+                continue;
+            }
+            _ => {}
+        }
+        let length = u16::from_be_bytes([*data.get(i + 2)?, *data.get(i + 3)?]) as usize;
+        let is_start_of_frame =
+            (0xC0..=0xCF).contains(&marker) && !matches!(marker, 0xC4 | 0xC8 | 0xCC);
+        if is_start_of_frame {
+            let height = u16::from_be_bytes([*data.get(i + 5)?, *data.get(i + 6)?]) as u32;
+            let width = u16::from_be_bytes([*data.get(i + 7)?, *data.get(i + 8)?]) as u32;
+            return Some((width, height));
+        }
+        i += 2 + length;
+    }
+    None
+}
+
+impl YoutubeClient {
+    /// Sets a custom thumbnail (JPEG or PNG, at most 2 MB) for the video.
+    #[cfg_attr(feature = "tracing", instrument)]
+    pub async fn set_thumbnail(
+        &self,
+        video_id: &str,
+        path: impl AsRef<Path> + Debug,
+    ) -> Result<ThumbnailSetResponse, YoutubeError> {
+        let path = path.as_ref();
+        let size = std::fs::metadata(path)?.len();
+        if size > MAX_THUMBNAIL_BYTES {
+            return Err(YoutubeError::InvalidThumbnail(ThumbnailError::TooLarge {
+                size,
+                max: MAX_THUMBNAIL_BYTES,
+            }));
+        }
+        let data = std::fs::read(path)?;
+        let format = ThumbnailFormat::detect(&data).ok_or(YoutubeError::InvalidThumbnail(
+            ThumbnailError::UnsupportedFormat,
+        ))?;
+        match format.dimensions(&data) {
+            Some(dimensions) if dimensions != RECOMMENDED_THUMBNAIL_SIZE => warn!(
+                "thumbnail {} is {}x{}, the recommended size is {}x{}",
+                path.display(),
+                dimensions.0,
+                dimensions.1,
+                RECOMMENDED_THUMBNAIL_SIZE.0,
+                RECOMMENDED_THUMBNAIL_SIZE.1
+            ),
+            Some(_) => {}
+            None => warn!("could not read the dimensions of {}", path.display()),
+        }
+
+        struct ThumbnailParams {
+            video_id: String,
+            data: Vec<u8>,
+            format: ThumbnailFormat,
+            content_owner: Option<ContentOwner>,
+        }
+        async fn set_thumbnail(
+            client: &YouTube<HttpsConnector<HttpConnector>>,
+            params: &ThumbnailParams,
+        ) -> google_youtube3::Result<(Response<Body>, ThumbnailSetResponse)> {
+            let mut call = client.thumbnails().set(&params.video_id);
+            if let Some(owner) = &params.content_owner {
+                call = call.on_behalf_of_content_owner(&owner.owner);
+            }
+            call.add_scope(Scope::Upload)
+                .upload(
+                    Cursor::new(params.data.clone()),
+                    params.format.mime_type().parse().unwrap(),
+                )
+                .await
+        }
+        let params = ThumbnailParams {
+            video_id: video_id.to_string(),
+            data,
+            format,
+            content_owner: self.content_owner.clone(),
+        };
+        self.prepare_call(Scope::Upload, QuotaOperation::ThumbnailSet)
+            .await?;
+        let result = generic_check_backoff_youtube(&self.client, &params, set_thumbnail)
+            .await
+            .map_err(YoutubeError::backoff)?;
+        let (res, response) = match result {
+            Ok(result) => result,
+            Err(e) => return Err(YoutubeError::from_upload(e).await),
+        };
+        if res.status().is_success() {
+            Ok(response)
+        } else {
+            Err(YoutubeError::status(res).await)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOI: [u8; 2] = [0xFF, 0xD8];
+
+    fn segment(marker: u8, payload: &[u8]) -> Vec<u8> {
+        let length = (payload.len() + 2) as u16;
+        let mut segment = vec![0xFF, marker];
+        segment.extend_from_slice(&length.to_be_bytes());
+        segment.extend_from_slice(payload);
+        segment
+    }
+
+    /// A start of frame for a 1280x720 image with 3 components.
+    fn start_of_frame(marker: u8) -> Vec<u8> {
+        let mut payload = vec![8, 0x02, 0xD0, 0x05, 0x00, 3];
+        payload.extend_from_slice(&[1, 0x22, 0, 2, 0x11, 1, 3, 0x11, 1]);
+        segment(marker, &payload)
+    }
+
+    fn jpeg(segments: &[Vec<u8>]) -> Vec<u8> {
+        let mut data = SOI.to_vec();
+        for segment in segments {
+            data.extend_from_slice(segment);
+        }
+        data
+    }
+
+    #[test]
+    fn reads_baseline_and_progressive_dimensions() {
+        let app0 = segment(0xE0, b"JFIF\0\x01\x01\0\0\x01\0\x01\0\0");
+        // a huffman table uses one of the SOF marker numbers but is none
+        let dht = segment(0xC4, &[0; 20]);
+        for marker in [0xC0, 0xC2] {
+            let data = jpeg(&[app0.clone(), dht.clone(), start_of_frame(marker)]);
+            assert_eq!(ThumbnailFormat::detect(&data), Some(ThumbnailFormat::Jpeg));
+            assert_eq!(jpeg_dimensions(&data), Some((1280, 720)));
+        }
+    }
+
+    #[test]
+    fn skips_standalone_markers_and_fill_bytes() {
+        let mut data = jpeg(&[segment(0xE0, &[0; 14])]);
+        // a restart marker, a TEM marker and a fill byte, none of them has a length
+        data.extend_from_slice(&[0xFF, 0xD0, 0xFF, 0x01, 0xFF]);
+        data.extend_from_slice(&start_of_frame(0xC0));
+        assert_eq!(jpeg_dimensions(&data), Some((1280, 720)));
+    }
+
+    #[test]
+    fn truncated_file_has_no_dimensions() {
+        let data = jpeg(&[segment(0xE0, &[0; 14]), start_of_frame(0xC0)]);
+        // cut in the middle of the start of frame
+        let sof_start = data.len() - start_of_frame(0xC0).len();
+        for len in [3, 5, sof_start + 1, sof_start + 6, sof_start + 8] {
+            assert_eq!(jpeg_dimensions(&data[..len]), None, "length {}", len);
+        }
+        // the segment length points past the end of the file
+        let data = jpeg(&[segment(0xE0, &[0; 14])[..6].to_vec()]);
+        assert_eq!(jpeg_dimensions(&data), None);
+    }
+
+    #[test]
+    fn garbage_between_segments_is_rejected() {
+        let mut data = jpeg(&[segment(0xE0, &[0; 14])]);
+        data.push(0x00);
+        data.extend_from_slice(&start_of_frame(0xC0));
+        assert_eq!(jpeg_dimensions(&data), None);
+    }
+}
@@ -0,0 +1,301 @@
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+
+use chrono::{DateTime, TimeZone, Utc};
+use google_youtube3::{
+    self as youtube,
+    hyper::{self, client::HttpConnector, header, Body, Request, Response},
+    hyper_rustls::HttpsConnector,
+    oauth2::{
+        self,
+        error::{AuthError, AuthErrorCode},
+    },
+};
+use serde::Deserialize;
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+#[cfg(feature = "tracing")]
+use tracing::instrument;
+
+use crate::prelude::*;
+use crate::token_store::{TokenStore, UserTokenStorage};
+use crate::{YoutubeClient, YoutubeError};
+
+/// How long to wait before trying again after a refresh failed, also the
+/// shortest time between two refreshes.
+const RETRY_INTERVAL: Duration = Duration::from_secs(60);
+
+/// What is known about the current access token, without the token itself.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccessTokenInfo {
+    pub expires_at: Option<DateTime<Utc>>,
+    /// The scopes google granted the token for.
+    pub scopes: Vec<String>,
+    /// The email of the account, only known if the `email` scope was granted.
+    pub email: Option<String>,
+}
+
+/// Sent by the background refresh of [`YoutubeClient::spawn_token_refresh`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TokenEvent {
+    Refreshed {
+        expires_at: Option<DateTime<Utc>>,
+    },
+    /// The refresh failed for a reason that might go away, it is retried.
+    RefreshFailed(String),
+    /// The refresh token is no longer valid, the user has to authorize the application again.
+    Revoked,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenInfoResponse {
+    #[serde(default)]
+    scope: String,
+    email: Option<String>,
+    exp: Option<String>,
+}
+
+fn expiry(token: &oauth2::AccessToken) -> Option<DateTime<Utc>> {
+    let expiration = token.expiration_time()?;
+    Utc.timestamp_opt(expiration.unix_timestamp(), 0).single()
+}
+
+/// Encodes everything but the unreserved characters for a form body.
+fn form_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (byte as char).to_string()
+            }
+            byte => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+/// Posts a form to one of the oauth endpoints of google through the given client,
+/// so the connector of the [`YoutubeClient`] is used for these requests as well.
+pub(crate) async fn post_form(
+    http: &hyper::Client<HttpsConnector<HttpConnector>>,
+    url: &str,
+    form: &[(&str, &str)],
+) -> Result<Response<Body>, YoutubeError> {
+    let body = form
+        .iter()
+        .map(|(key, value)| format!("{}={}", form_encode(key), form_encode(value)))
+        .collect::<Vec<_>>()
+        .join("&");
+    let request = Request::post(url)
+        .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+        .body(Body::from(body))
+        .map_err(YoutubeError::auth)?;
+    http.request(request)
+        .await
+        .map_err(|e| YoutubeError::from(youtube::Error::HttpError(e)))
+}
+
+/// Revokes the stored tokens of the user at `revoke_url` and clears them from the store.
+///
+/// Works straight on the store, so no authenticator is needed to log a user out.
+pub(crate) async fn revoke_tokens(
+    http: &hyper::Client<HttpsConnector<HttpConnector>>,
+    store: &Arc<dyn TokenStore>,
+    user: &str,
+    revoke_url: &str,
+) -> Result<(), YoutubeError> {
+    let storage = UserTokenStorage::new(store.clone(), user);
+    let tokens = storage.revocable_tokens().await.unwrap_or_else(|e| {
+        warn!(
+            "could not load the tokens of {} to revoke them: {}",
+            user, e
+        );
+        Vec::new()
+    });
+    if tokens.is_empty() {
+        info!("no stored tokens to revoke for {}", user);
+    }
+    for token in tokens {
+        let response = post_form(http, revoke_url, &[("token", &token)]).await?;
+        // google answers 400 for a token that is already revoked or expired
+        if response.status() == hyper::StatusCode::BAD_REQUEST {
+            warn!("the token of {} was already invalid", user);
+        } else if !response.status().is_success() {
+            return Err(YoutubeError::status(response).await);
+        }
+    }
+    store.clear(user).await.map_err(YoutubeError::auth)?;
+    info!("logged out {}", user);
+    Ok(())
+}
+
+fn is_revoked(e: &oauth2::Error) -> bool {
+    matches!(
+        e,
+        oauth2::Error::AuthError(AuthError {
+            error: AuthErrorCode::InvalidGrant,
+            ..
+        })
+    )
+}
+
+impl YoutubeClient {
+    /// The scope urls the current token is requested for.
+    pub(crate) fn scope_urls(&self) -> Vec<String> {
+        let mut urls: Vec<String> = self.scopes().urls().into_iter().map(String::from).collect();
+        urls.extend(self.extra_scopes.iter().cloned());
+        urls
+    }
+
+    /// Asks google what the current access token is good for.
+    #[cfg_attr(feature = "tracing", instrument)]
+    pub async fn token_info(&self) -> Result<AccessTokenInfo, YoutubeError> {
+        let token = self
+            .authenticator
+            .token(&self.scope_urls())
+            .await
+            .map_err(YoutubeError::auth)?;
+        let access_token = token
+            .token()
+            .ok_or_else(|| YoutubeError::auth("the authenticator returned no access token"))?;
+        let response = post_form(
+            &self.client.client,
+            &self.config.token_info_url,
+            &[("access_token", access_token)],
+        )
+        .await?;
+        if !response.status().is_success() {
+            return Err(YoutubeError::status(response).await);
+        }
+        let body = hyper::body::to_bytes(response.into_body())
+            .await
+            .map_err(|e| YoutubeError::from(youtube::Error::HttpError(e)))?;
+        let response: TokenInfoResponse =
+            serde_json::from_slice(&body).map_err(YoutubeError::auth)?;
+
+        let expires_at = response
+            .exp
+            .and_then(|exp| exp.parse().ok())
+            .and_then(|exp| Utc.timestamp_opt(exp, 0).single())
+            .or_else(|| expiry(&token));
+        Ok(AccessTokenInfo {
+            expires_at,
+            scopes: response
+                .scope
+                .split_whitespace()
+                .map(String::from)
+                .collect(),
+            email: response.email,
+        })
+    }
+
+    /// The user the tokens of this client belong to.
+    pub fn user(&self) -> &str {
+        &self.user
+    }
+
+    /// Revokes the tokens of the user at google and removes them from the token store.
+    ///
+    /// Afterwards the client can only be used again once the user authorized the application again.
+    ///
+    /// Fails with [`YoutubeError::NoTokenStore`] for a client with a provided authenticator
+    /// or an [`AuthMode::AccessToken`](crate::AuthMode::AccessToken), their tokens are not
+    /// in the store and whatever is stored for the user belongs to someone else.
+    #[cfg_attr(feature = "tracing", instrument)]
+    pub async fn logout(&self) -> Result<(), YoutubeError> {
+        let token_store = self
+            .token_store
+            .as_ref()
+            .ok_or(YoutubeError::NoTokenStore)?;
+        revoke_tokens(
+            &self.client.client,
+            token_store,
+            &self.user,
+            &self.config.revoke_url,
+        )
+        .await?;
+        self.invalidate_playlist_cache().await;
+        // nobody listening is fine
+        let _ = self.token_events.send(TokenEvent::Revoked);
+        Ok(())
+    }
+
+    /// Events of the background token refresh.
+    pub fn token_events(&self) -> broadcast::Receiver<TokenEvent> {
+        self.token_events.subscribe()
+    }
+
+    /// Refreshes the access token `margin` before it expires, so long running
+    /// uploads never run into an expired token.
+    ///
+    /// The margin has to be well below the lifetime of the tokens (about an hour
+    /// at google), refreshes are at least a minute apart either way.
+    ///
+    /// The task ends when the client is dropped or the refresh token was revoked.
+    /// A client that was given an [`AuthMode::AccessToken`](crate::AuthMode::AccessToken)
+    /// has nothing to refresh with, the task only sends a [`TokenEvent::RefreshFailed`] then.
+    /// The same goes for a provided authenticator unless it was marked with
+    /// [`YoutubeClientBuilder::authenticator_refreshable`](crate::YoutubeClientBuilder::authenticator_refreshable).
+    pub fn spawn_token_refresh(self: &Arc<Self>, margin: Duration) -> JoinHandle<()> {
+        let client = Arc::downgrade(self);
+        tokio::spawn(refresh_tokens(client, margin))
+    }
+}
+
+async fn refresh_tokens(client: Weak<YoutubeClient>, margin: Duration) {
+    if let Some(client) = client.upgrade() {
+        if !client.refreshable {
+            warn!("the access token of {} can not be refreshed", client.user);
+            let _ = client.token_events.send(TokenEvent::RefreshFailed(
+                "the client has no refresh token".to_string(),
+            ));
+            return;
+        }
+    }
+    let mut force = false;
+    loop {
+        let wait = {
+            let client = match client.upgrade() {
+                Some(client) => client,
+                None => return,
+            };
+            let scopes = client.scope_urls();
+            let token = if force {
+                client.authenticator.force_refreshed_token(&scopes).await
+            } else {
+                client.authenticator.token(&scopes).await
+            };
+            match token {
+                Ok(token) => {
+                    let expires_at = expiry(&token);
+                    if force {
+                        debug!("refreshed the access token, it expires at {:?}", expires_at);
+                        // nobody listening is fine
+                        let _ = client
+                            .token_events
+                            .send(TokenEvent::Refreshed { expires_at });
+                    }
+                    expires_at
+                        .and_then(|expires_at| (expires_at - Utc::now()).to_std().ok())
+                        .map(|left| left.saturating_sub(margin))
+                        .unwrap_or_default()
+                        // a margin longer than the token lifetime would refresh without a pause
+                        .max(RETRY_INTERVAL)
+                }
+                Err(e) if is_revoked(&e) => {
+                    error!("the refresh token was revoked: {}", e);
+                    let _ = client.token_events.send(TokenEvent::Revoked);
+                    return;
+                }
+                Err(e) => {
+                    warn!("could not refresh the access token: {}", e);
+                    let _ = client
+                        .token_events
+                        .send(TokenEvent::RefreshFailed(e.to_string()));
+                    RETRY_INTERVAL
+                }
+            }
+        };
+        force = true;
+        tokio::time::sleep(wait).await;
+    }
+}
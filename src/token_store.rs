@@ -0,0 +1,630 @@
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use google_youtube3::oauth2::storage::{TokenInfo, TokenStorage};
+use serde::{Deserialize, Serialize};
+use strfmt::strfmt;
+use tokio::sync::Mutex;
+
+use crate::prelude::*;
+use crate::scopes::url_covers;
+
+/// Persists the tokens of each user.
+///
+/// A store only moves opaque bytes around, which tokens are in there and for
+/// which scopes they were granted is handled by the client.
+#[async_trait]
+pub trait TokenStore: Debug + Send + Sync {
+    /// The stored data of the user, `None` if nothing was stored yet.
+    async fn load(&self, user: &str) -> Result<Option<Vec<u8>>>;
+
+    async fn save(&self, user: &str, data: &[u8]) -> Result<()>;
+
+    /// Removes everything that is stored for the user.
+    async fn clear(&self, user: &str) -> Result<()>;
+}
+
+/// Stores the tokens of each user in a plain json file.
+///
+/// The path is a template, `{user}` is replaced with the name of the user.
+#[derive(Debug, Clone)]
+pub struct FileTokenStore {
+    path_template: String,
+}
+
+impl FileTokenStore {
+    pub fn new(path_template: impl Into<String>) -> Self {
+        Self {
+            path_template: path_template.into(),
+        }
+    }
+
+    pub fn path_for(&self, user: &str) -> Result<PathBuf> {
+        let mut vars = HashMap::new();
+        vars.insert("user".to_string(), user.to_string());
+        let path = strfmt(&self.path_template, &vars)
+            .map_err(|e| anyhow!("Error formatting path: {}", e))?;
+        Ok(PathBuf::from(path))
+    }
+}
+
+async fn read_if_exists(path: &Path) -> Result<Option<Vec<u8>>> {
+    match tokio::fs::read(path).await {
+        Ok(data) => Ok(Some(data)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+async fn write_creating_dirs(path: &Path, data: &[u8]) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    tokio::fs::write(path, data).await?;
+    Ok(())
+}
+
+async fn remove_if_exists(path: &Path) -> Result<()> {
+    match tokio::fs::remove_file(path).await {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+#[async_trait]
+impl TokenStore for FileTokenStore {
+    async fn load(&self, user: &str) -> Result<Option<Vec<u8>>> {
+        read_if_exists(&self.path_for(user)?).await
+    }
+
+    async fn save(&self, user: &str, data: &[u8]) -> Result<()> {
+        let path = self.path_for(user)?;
+        debug!("saving tokens for user {} to {}", user, path.display());
+        write_creating_dirs(&path, data).await
+    }
+
+    async fn clear(&self, user: &str) -> Result<()> {
+        remove_if_exists(&self.path_for(user)?).await
+    }
+}
+
+/// Keeps the tokens in memory only, for read-only environments.
+#[derive(Debug, Default)]
+pub struct MemoryTokenStore {
+    tokens: std::sync::Mutex<HashMap<String, Vec<u8>>>,
+}
+
+impl MemoryTokenStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl TokenStore for MemoryTokenStore {
+    async fn load(&self, user: &str) -> Result<Option<Vec<u8>>> {
+        Ok(self.tokens.lock().unwrap().get(user).cloned())
+    }
+
+    async fn save(&self, user: &str, data: &[u8]) -> Result<()> {
+        self.tokens
+            .lock()
+            .unwrap()
+            .insert(user.to_string(), data.to_vec());
+        Ok(())
+    }
+
+    async fn clear(&self, user: &str) -> Result<()> {
+        self.tokens.lock().unwrap().remove(user);
+        Ok(())
+    }
+}
+
+#[cfg(feature = "token-encryption")]
+pub use encrypted::{EncryptedFileTokenStore, EncryptionKey};
+
+#[cfg(feature = "token-encryption")]
+mod encrypted {
+    use super::*;
+    use chacha20poly1305::aead::rand_core::RngCore;
+    use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+    use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+
+    const SALT_LEN: usize = 16;
+    const NONCE_LEN: usize = 12;
+
+    /// Where the key to encrypt the tokens with comes from.
+    #[derive(Clone)]
+    pub enum EncryptionKey {
+        /// The key is derived from the passphrase with argon2.
+        Passphrase(String),
+        /// A file that contains the 32 byte key.
+        KeyFile(PathBuf),
+    }
+
+    impl Debug for EncryptionKey {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            match self {
+                EncryptionKey::Passphrase(_) => f.write_str("Passphrase(..)"),
+                EncryptionKey::KeyFile(path) => f.debug_tuple("KeyFile").field(path).finish(),
+            }
+        }
+    }
+
+    /// Like the [`FileTokenStore`], but the files are encrypted with ChaCha20-Poly1305.
+    ///
+    /// Each file starts with the salt and the nonce, followed by the ciphertext.
+    /// The key of the last salt is kept in memory, so argon2 only runs when the salt changes.
+    #[derive(Clone)]
+    pub struct EncryptedFileTokenStore {
+        files: FileTokenStore,
+        key: EncryptionKey,
+        derived: Arc<Mutex<Option<([u8; SALT_LEN], [u8; 32])>>>,
+    }
+
+    impl Debug for EncryptedFileTokenStore {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.debug_struct("EncryptedFileTokenStore")
+                .field("files", &self.files)
+                .field("key", &self.key)
+                .finish()
+        }
+    }
+
+    impl EncryptedFileTokenStore {
+        pub fn new(path_template: impl Into<String>, key: EncryptionKey) -> Self {
+            Self {
+                files: FileTokenStore::new(path_template),
+                key,
+                derived: Arc::new(Mutex::new(None)),
+            }
+        }
+
+        /// The salt of the cached key, a new one if nothing was derived yet.
+        async fn salt(&self) -> [u8; SALT_LEN] {
+            match *self.derived.lock().await {
+                Some((salt, _)) => salt,
+                None => {
+                    let mut salt = [0u8; SALT_LEN];
+                    OsRng.fill_bytes(&mut salt);
+                    salt
+                }
+            }
+        }
+
+        async fn cipher(&self, salt: [u8; SALT_LEN]) -> Result<ChaCha20Poly1305> {
+            let mut derived = self.derived.lock().await;
+            if let Some((cached_salt, key)) = derived.as_ref() {
+                if *cached_salt == salt {
+                    return Ok(ChaCha20Poly1305::new(Key::from_slice(key)));
+                }
+            }
+            let key = match &self.key {
+                EncryptionKey::Passphrase(passphrase) => {
+                    let passphrase = passphrase.clone();
+                    // argon2 is slow on purpose, keep it off the async threads
+                    tokio::task::spawn_blocking(move || {
+                        let mut key = [0u8; 32];
+                        argon2::Argon2::default()
+                            .hash_password_into(passphrase.as_bytes(), &salt, &mut key)
+                            .map(|_| key)
+                    })
+                    .await?
+                    .map_err(|e| anyhow!("could not derive the token key: {}", e))?
+                }
+                EncryptionKey::KeyFile(path) => {
+                    let data = tokio::fs::read(path).await?;
+                    data.get(..32)
+                        .and_then(|key| key.try_into().ok())
+                        .ok_or_else(|| anyhow!("the key file {} is too short", path.display()))?
+                }
+            };
+            *derived = Some((salt, key));
+            Ok(ChaCha20Poly1305::new(Key::from_slice(&key)))
+        }
+    }
+
+    #[async_trait]
+    impl TokenStore for EncryptedFileTokenStore {
+        async fn load(&self, user: &str) -> Result<Option<Vec<u8>>> {
+            let data = match self.files.load(user).await? {
+                Some(data) => data,
+                None => return Ok(None),
+            };
+            if data.len() < SALT_LEN + NONCE_LEN {
+                return Err(anyhow!("the token file of {} is truncated", user));
+            }
+            let (salt, rest) = data.split_at(SALT_LEN);
+            let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+            let salt = salt
+                .try_into()
+                .expect("the salt was split off at its length");
+            let plaintext = self
+                .cipher(salt)
+                .await?
+                .decrypt(Nonce::from_slice(nonce), ciphertext)
+                .map_err(|_| anyhow!("could not decrypt the tokens of {}", user))?;
+            Ok(Some(plaintext))
+        }
+
+        async fn save(&self, user: &str, data: &[u8]) -> Result<()> {
+            // the nonce is new for every file, so the salt and its key can be reused
+            let salt = self.salt().await;
+            let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+            let ciphertext = self
+                .cipher(salt)
+                .await?
+                .encrypt(&nonce, data)
+                .map_err(|_| anyhow!("could not encrypt the tokens of {}", user))?;
+            let mut file = Vec::with_capacity(SALT_LEN + NONCE_LEN + ciphertext.len());
+            file.extend_from_slice(&salt);
+            file.extend_from_slice(&nonce);
+            file.extend_from_slice(&ciphertext);
+            self.files.save(user, &file).await
+        }
+
+        async fn clear(&self, user: &str) -> Result<()> {
+            self.files.clear(user).await
+        }
+    }
+}
+
+#[cfg(feature = "sqlite")]
+pub use sqlite::SqliteTokenStore;
+
+#[cfg(feature = "sqlite")]
+mod sqlite {
+    use super::*;
+    use rusqlite::{params, Connection, OptionalExtension};
+
+    /// Stores the tokens of all users in a single sqlite database.
+    ///
+    /// The queries run on the blocking thread pool.
+    #[derive(Debug, Clone)]
+    pub struct SqliteTokenStore {
+        connection: Arc<std::sync::Mutex<Connection>>,
+    }
+
+    impl SqliteTokenStore {
+        pub fn open(path: impl AsRef<std::path::Path>) -> Result<Self> {
+            Self::from_connection(Connection::open(path)?)
+        }
+
+        pub fn from_connection(connection: Connection) -> Result<Self> {
+            connection.execute(
+                "CREATE TABLE IF NOT EXISTS youtube_tokens (user TEXT PRIMARY KEY, data BLOB NOT NULL)",
+                [],
+            )?;
+            Ok(Self {
+                connection: Arc::new(std::sync::Mutex::new(connection)),
+            })
+        }
+
+        async fn with_connection<T, F>(&self, f: F) -> Result<T>
+        where
+            T: Send + 'static,
+            F: FnOnce(&Connection) -> rusqlite::Result<T> + Send + 'static,
+        {
+            let connection = self.connection.clone();
+            let result = tokio::task::spawn_blocking(move || {
+                let connection = connection.lock().unwrap_or_else(|e| e.into_inner());
+                f(&connection)
+            })
+            .await??;
+            Ok(result)
+        }
+    }
+
+    #[async_trait]
+    impl TokenStore for SqliteTokenStore {
+        async fn load(&self, user: &str) -> Result<Option<Vec<u8>>> {
+            let user = user.to_string();
+            self.with_connection(move |connection| {
+                connection
+                    .query_row(
+                        "SELECT data FROM youtube_tokens WHERE user = ?1",
+                        params![user],
+                        |row| row.get(0),
+                    )
+                    .optional()
+            })
+            .await
+        }
+
+        async fn save(&self, user: &str, data: &[u8]) -> Result<()> {
+            let user = user.to_string();
+            let data = data.to_vec();
+            self.with_connection(move |connection| {
+                connection.execute(
+                    "INSERT INTO youtube_tokens (user, data) VALUES (?1, ?2) \
+                     ON CONFLICT(user) DO UPDATE SET data = excluded.data",
+                    params![user, data],
+                )
+            })
+            .await?;
+            Ok(())
+        }
+
+        async fn clear(&self, user: &str) -> Result<()> {
+            let user = user.to_string();
+            self.with_connection(move |connection| {
+                connection.execute("DELETE FROM youtube_tokens WHERE user = ?1", params![user])
+            })
+            .await?;
+            Ok(())
+        }
+    }
+}
+
+/// A token together with the scopes it was granted for, the same format the
+/// token files of yup-oauth2 use.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ScopedToken {
+    scopes: Vec<String>,
+    token: TokenInfo,
+}
+
+/// Connects a [`TokenStore`] to the authenticator for a single user.
+#[derive(Debug)]
+pub(crate) struct UserTokenStorage {
+    store: Arc<dyn TokenStore>,
+    user: String,
+    // set reads and writes the whole list, two refreshes must not interleave
+    lock: Mutex<()>,
+}
+
+impl UserTokenStorage {
+    pub(crate) fn new(store: Arc<dyn TokenStore>, user: impl Into<String>) -> Self {
+        Self {
+            store,
+            user: user.into(),
+            lock: Mutex::new(()),
+        }
+    }
+
+    async fn tokens(&self) -> Result<Vec<ScopedToken>> {
+        match self.store.load(&self.user).await? {
+            Some(data) => Ok(serde_json::from_slice(&data)?),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// The tokens that have to be revoked to log the user out, revoking a
+    /// refresh token revokes the access tokens issued for it as well.
+    pub(crate) async fn revocable_tokens(&self) -> Result<Vec<String>> {
+        let mut tokens = Vec::new();
+        for stored in self.tokens().await? {
+            let token = stored.token.refresh_token.or(stored.token.access_token);
+            if let Some(token) = token.filter(|token| !tokens.contains(token)) {
+                tokens.push(token);
+            }
+        }
+        Ok(tokens)
+    }
+}
+
+fn same_scopes(a: &[String], b: &[&str]) -> bool {
+    a.len() == b.len() && b.iter().all(|scope| a.iter().any(|s| s == scope))
+}
+
+#[async_trait]
+impl TokenStorage for UserTokenStorage {
+    async fn set(&self, scopes: &[&str], token: TokenInfo) -> anyhow::Result<()> {
+        let _guard = self.lock.lock().await;
+        let mut tokens = self.tokens().await.unwrap_or_else(|e| {
+            warn!("discarding unreadable tokens of {}: {}", self.user, e);
+            Vec::new()
+        });
+        tokens.retain(|stored| !same_scopes(&stored.scopes, scopes));
+        tokens.push(ScopedToken {
+            scopes: scopes.iter().map(|s| s.to_string()).collect(),
+            token,
+        });
+        let data = serde_json::to_vec(&tokens)?;
+        self.store.save(&self.user, &data).await
+    }
+
+    async fn get(&self, scopes: &[&str]) -> Option<TokenInfo> {
+        let tokens = self
+            .tokens()
+            .await
+            .map_err(|e| warn!("could not load the tokens of {}: {}", self.user, e))
+            .ok()?;
+        // prefer a token for exactly these scopes, any token that covers them works as well
+        let covers = |stored: &&ScopedToken| {
+            scopes
+                .iter()
+                .all(|scope| stored.scopes.iter().any(|s| url_covers(s, scope)))
+        };
+        tokens
+            .iter()
+            .find(|stored| same_scopes(&stored.scopes, scopes))
+            .or_else(|| tokens.iter().find(covers))
+            .map(|stored| stored.token.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scopes::{YOUTUBE, YOUTUBE_READONLY, YOUTUBE_UPLOAD};
+
+    fn token(access_token: &str) -> TokenInfo {
+        serde_json::from_value(serde_json::json!({
+            "access_token": access_token,
+            "refresh_token": format!("refresh-{}", access_token),
+            "expires_at": null,
+            "id_token": null,
+        }))
+        .unwrap()
+    }
+
+    fn storage() -> UserTokenStorage {
+        UserTokenStorage::new(Arc::new(MemoryTokenStore::new()), "user")
+    }
+
+    async fn access_token(storage: &UserTokenStorage, scopes: &[&str]) -> Option<String> {
+        storage
+            .get(scopes)
+            .await
+            .and_then(|token| token.access_token)
+    }
+
+    #[tokio::test]
+    async fn get_prefers_the_exact_scopes() {
+        let storage = storage();
+        storage.set(&[YOUTUBE], token("full")).await.unwrap();
+        storage
+            .set(&[YOUTUBE_UPLOAD, YOUTUBE_READONLY], token("exact"))
+            .await
+            .unwrap();
+        // the order of the scopes does not matter
+        assert_eq!(
+            access_token(&storage, &[YOUTUBE_READONLY, YOUTUBE_UPLOAD]).await,
+            Some("exact".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn get_falls_back_to_a_covering_token() {
+        let storage = storage();
+        storage.set(&[YOUTUBE], token("full")).await.unwrap();
+        assert_eq!(
+            access_token(&storage, &[YOUTUBE_UPLOAD]).await,
+            Some("full".to_string())
+        );
+        storage
+            .set(&[YOUTUBE_READONLY], token("readonly"))
+            .await
+            .unwrap();
+        assert_eq!(
+            access_token(&storage, &[YOUTUBE_UPLOAD, YOUTUBE_READONLY]).await,
+            Some("full".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn get_ignores_tokens_that_do_not_cover_the_scopes() {
+        let storage = storage();
+        storage
+            .set(&[YOUTUBE_READONLY], token("readonly"))
+            .await
+            .unwrap();
+        assert_eq!(access_token(&storage, &[YOUTUBE]).await, None);
+        assert_eq!(
+            access_token(&storage, &[YOUTUBE_READONLY, YOUTUBE_UPLOAD]).await,
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn set_replaces_only_the_same_scopes() {
+        let storage = storage();
+        storage.set(&[YOUTUBE], token("old")).await.unwrap();
+        storage
+            .set(&[YOUTUBE_READONLY], token("readonly"))
+            .await
+            .unwrap();
+        storage.set(&[YOUTUBE], token("new")).await.unwrap();
+        assert_eq!(
+            access_token(&storage, &[YOUTUBE]).await,
+            Some("new".to_string())
+        );
+        assert_eq!(
+            access_token(&storage, &[YOUTUBE_READONLY]).await,
+            Some("readonly".to_string())
+        );
+        assert_eq!(storage.tokens().await.unwrap().len(), 2);
+        assert_eq!(
+            storage.revocable_tokens().await.unwrap(),
+            vec!["refresh-readonly".to_string(), "refresh-new".to_string()]
+        );
+    }
+
+    #[tokio::test]
+    async fn reads_token_files_of_yup_oauth2() {
+        let file = format!(
+            r#"[{{"scopes":["{}"],"token":{{"access_token":"ya29.a0","refresh_token":"1//0g","expires_at":null,"id_token":null}}}}]"#,
+            YOUTUBE
+        );
+        let store = Arc::new(MemoryTokenStore::new());
+        store.save("user", file.as_bytes()).await.unwrap();
+        let storage = UserTokenStorage::new(store, "user");
+        let token = storage.get(&[YOUTUBE]).await.unwrap();
+        assert_eq!(token.access_token.as_deref(), Some("ya29.a0"));
+        assert_eq!(token.refresh_token.as_deref(), Some("1//0g"));
+    }
+
+    #[cfg(feature = "token-encryption")]
+    mod encrypted {
+        use super::super::*;
+
+        fn test_dir(name: &str) -> PathBuf {
+            let dir = std::env::temp_dir().join(format!(
+                "google_youtube-tokens-{}-{}",
+                name,
+                std::process::id()
+            ));
+            std::fs::create_dir_all(&dir).unwrap();
+            dir
+        }
+
+        fn store(dir: &Path, passphrase: &str) -> EncryptedFileTokenStore {
+            EncryptedFileTokenStore::new(
+                format!("{}/{{user}}.bin", dir.display()),
+                EncryptionKey::Passphrase(passphrase.to_string()),
+            )
+        }
+
+        #[tokio::test]
+        async fn round_trip() {
+            let dir = test_dir("round-trip");
+            let store = store(&dir, "secret");
+            store.save("user", b"tokens").await.unwrap();
+            let file = std::fs::read(dir.join("user.bin")).unwrap();
+            assert!(!file.windows(6).any(|w| w == b"tokens"));
+            assert_eq!(store.load("user").await.unwrap(), Some(b"tokens".to_vec()));
+            assert_eq!(store.load("nobody").await.unwrap(), None);
+            std::fs::remove_dir_all(dir).unwrap();
+        }
+
+        #[tokio::test]
+        async fn reuses_the_cached_salt() {
+            let dir = test_dir("cached-salt");
+            let store = store(&dir, "secret");
+            store.save("first", b"one").await.unwrap();
+            store.save("second", b"two").await.unwrap();
+            let first = std::fs::read(dir.join("first.bin")).unwrap();
+            let second = std::fs::read(dir.join("second.bin")).unwrap();
+            let (salt, nonce) = first.split_at(16);
+            assert_eq!(salt, &second[..16]);
+            // the key is reused, the nonce never is
+            assert_ne!(&nonce[..12], &second[16..28]);
+
+            // a new store derives the key again from the salt in the file
+            let fresh = self::store(&dir, "secret");
+            assert_eq!(fresh.load("second").await.unwrap(), Some(b"two".to_vec()));
+            std::fs::remove_dir_all(dir).unwrap();
+        }
+
+        #[tokio::test]
+        async fn wrong_passphrase_does_not_decrypt() {
+            let dir = test_dir("wrong-passphrase");
+            store(&dir, "secret").save("user", b"tokens").await.unwrap();
+            assert!(store(&dir, "guess").load("user").await.is_err());
+            std::fs::remove_dir_all(dir).unwrap();
+        }
+
+        #[tokio::test]
+        async fn truncated_file_is_an_error() {
+            let dir = test_dir("truncated");
+            std::fs::write(dir.join("user.bin"), [0u8; 10]).unwrap();
+            assert!(store(&dir, "secret").load("user").await.is_err());
+            std::fs::remove_dir_all(dir).unwrap();
+        }
+    }
+}
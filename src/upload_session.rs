@@ -0,0 +1,620 @@
+use std::collections::HashMap;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::UNIX_EPOCH;
+
+use exponential_backoff::youtube::generic_check_backoff_youtube;
+use google_youtube3::{
+    self as youtube,
+    api::Video,
+    client::{ContentRange, Delegate},
+    hyper::{self, client::HttpConnector, header, Body, Method, Request, Response, StatusCode},
+    hyper_rustls::HttpsConnector,
+    YouTube,
+};
+use serde::{Deserialize, Serialize};
+use tokio::sync::watch;
+
+use crate::prelude::*;
+use crate::progress::ProgressReader;
+use crate::{UploadProgress, YoutubeClient, YoutubeError};
+
+/// How much of the file is sent at once when an upload is resumed.
+const RESUME_CHUNK_SIZE: usize = 1024 * 1024;
+
+/// A resumable upload session that was started but not finished yet.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct UploadSession {
+    pub session_uri: String,
+    /// Offset up to which the server has confirmed the upload.
+    pub confirmed_offset: u64,
+}
+
+/// Persists resumable upload sessions to a small json file, so an upload can
+/// be resumed after the process was restarted.
+///
+/// Sessions are keyed by the path of the file together with a fingerprint of
+/// its size and modification time, so a changed file never resumes an old session.
+#[derive(Debug)]
+pub(crate) struct UploadSessionStore {
+    path: PathBuf,
+    lock: Mutex<()>,
+}
+
+impl UploadSessionStore {
+    pub(crate) fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            lock: Mutex::new(()),
+        }
+    }
+
+    pub(crate) fn key_for(file: &Path) -> std::io::Result<String> {
+        let metadata = std::fs::metadata(file)?;
+        let modified = metadata
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+        let file = std::fs::canonicalize(file)?;
+        Ok(format!(
+            "{}#{}:{}",
+            file.display(),
+            metadata.len(),
+            modified
+        ))
+    }
+
+    fn load(&self) -> HashMap<String, UploadSession> {
+        match std::fs::read_to_string(&self.path) {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+                warn!(
+                    "could not parse upload sessions in {}: {}",
+                    self.path.display(),
+                    e
+                );
+                HashMap::new()
+            }),
+            Err(_) => HashMap::new(),
+        }
+    }
+
+    fn save(&self, sessions: &HashMap<String, UploadSession>) {
+        if let Some(parent) = self.path.parent() {
+            if let Err(e) = std::fs::create_dir_all(parent) {
+                warn!("could not create dir for upload sessions: {}", e);
+            }
+        }
+        let res = serde_json::to_string_pretty(sessions)
+            .map_err(std::io::Error::from)
+            .and_then(|content| std::fs::write(&self.path, content));
+        if let Err(e) = res {
+            warn!(
+                "could not save upload sessions to {}: {}",
+                self.path.display(),
+                e
+            );
+        }
+    }
+
+    fn modify(&self, f: impl FnOnce(&mut HashMap<String, UploadSession>)) {
+        let _guard = self.lock.lock().unwrap_or_else(|e| e.into_inner());
+        let mut sessions = self.load();
+        f(&mut sessions);
+        self.save(&sessions);
+    }
+
+    pub(crate) fn get(&self, key: &str) -> Option<UploadSession> {
+        let _guard = self.lock.lock().unwrap_or_else(|e| e.into_inner());
+        self.load().remove(key)
+    }
+
+    pub(crate) fn set(&self, key: &str, session: UploadSession) {
+        self.modify(|sessions| {
+            sessions.insert(key.to_string(), session);
+        });
+    }
+
+    pub(crate) fn set_confirmed_offset(&self, key: &str, offset: u64) {
+        self.modify(|sessions| {
+            if let Some(session) = sessions.get_mut(key) {
+                session.confirmed_offset = offset;
+            }
+        });
+    }
+
+    pub(crate) fn remove(&self, key: &str) {
+        self.modify(|sessions| {
+            sessions.remove(key);
+        });
+    }
+}
+
+/// Keeps the store up to date while an upload runs, so
+/// [`YoutubeClient::resume_upload`] can pick up the session after a restart.
+pub(crate) struct UploadSessionDelegate<'a> {
+    store: &'a UploadSessionStore,
+    key: String,
+}
+
+impl<'a> UploadSessionDelegate<'a> {
+    pub(crate) fn new(store: &'a UploadSessionStore, key: String) -> Self {
+        Self { store, key }
+    }
+}
+
+impl Delegate for UploadSessionDelegate<'_> {
+    fn store_upload_url(&mut self, url: Option<&str>) {
+        match url {
+            Some(url) => {
+                debug!("storing upload session for {}", self.key);
+                self.store.set(
+                    &self.key,
+                    UploadSession {
+                        session_uri: url.to_string(),
+                        confirmed_offset: 0,
+                    },
+                )
+            }
+            None => self.store.remove(&self.key),
+        }
+    }
+
+    fn cancel_chunk_upload(&mut self, chunk: &ContentRange) -> bool {
+        // a chunk is only sent once everything before it was confirmed
+        if let Some(range) = &chunk.range {
+            self.store.set_confirmed_offset(&self.key, range.first);
+        }
+        false
+    }
+}
+
+/// Whether the error means the stored session is gone and the upload has to start over.
+pub(crate) fn is_expired_session(e: &youtube::Error) -> bool {
+    let status = match e {
+        youtube::Error::Failure(response) => response.status().as_u16(),
+        youtube::Error::BadRequest(value) => value["error"]["code"].as_u64().unwrap_or(0) as u16,
+        _ => return false,
+    };
+    status == StatusCode::NOT_FOUND.as_u16() || status == StatusCode::GONE.as_u16()
+}
+
+/// What the server knows about a session, see [`YoutubeClient::probe_session`].
+enum SessionState {
+    Complete(Video),
+    /// The server has everything before `offset`.
+    Incomplete {
+        offset: u64,
+    },
+    Expired,
+}
+
+/// Reads the offset after the last received byte from a `Range: bytes=0-N` header.
+fn confirmed_offset(response: &Response<Body>) -> u64 {
+    response
+        .headers()
+        .get(header::RANGE)
+        .and_then(|range| range.to_str().ok())
+        .and_then(|range| range.rsplit('-').next())
+        .and_then(|last| last.parse::<u64>().ok())
+        .map(|last| last + 1)
+        .unwrap_or(0)
+}
+
+async fn read_video(response: Response<Body>) -> youtube::Result<Video> {
+    let body = hyper::body::to_bytes(response.into_body())
+        .await
+        .map_err(youtube::Error::HttpError)?;
+    serde_json::from_slice(&body).map_err(|e| {
+        youtube::Error::JsonDecodeError(String::from_utf8_lossy(&body).into_owned(), e)
+    })
+}
+
+/// The error the generated calls return for a failed response, so the backoff
+/// treats a failed resume the same way as a failed insert.
+async fn failure(response: Response<Body>) -> youtube::Error {
+    let (parts, body) = response.into_parts();
+    let body = match hyper::body::to_bytes(body).await {
+        Ok(body) => body,
+        Err(e) => return youtube::Error::HttpError(e),
+    };
+    match serde_json::from_slice(&body) {
+        Ok(value) => youtube::Error::BadRequest(value),
+        Err(_) => youtube::Error::Failure(Response::from_parts(parts, Body::from(body))),
+    }
+}
+
+struct ResumeParams<'a> {
+    client: &'a YoutubeClient,
+    store: &'a UploadSessionStore,
+    key: &'a str,
+    session_uri: String,
+    path: &'a Path,
+    progress: Option<&'a watch::Sender<UploadProgress>>,
+}
+
+async fn resume_attempt(
+    _youtube: &YouTube<HttpsConnector<HttpConnector>>,
+    params: &ResumeParams<'_>,
+) -> youtube::Result<Option<Video>> {
+    params.client.continue_session(params).await
+}
+
+impl YoutubeClient {
+    /// Continues the persisted upload session of the file where the server left off.
+    ///
+    /// Every attempt probes the session first and sends the rest of the file from
+    /// there, failed attempts are retried with the same backoff as every other call.
+    /// The session is only dropped once the server says it expired.
+    ///
+    /// Returns `None` if there is no session or it expired, the upload then has to start over.
+    pub(crate) async fn resume_upload(
+        &self,
+        store: &UploadSessionStore,
+        key: &str,
+        path: &Path,
+        progress: Option<&watch::Sender<UploadProgress>>,
+    ) -> Result<Option<Video>, YoutubeError> {
+        let session = match store.get(key) {
+            Some(session) => session,
+            None => return Ok(None),
+        };
+        let params = ResumeParams {
+            client: self,
+            store,
+            key,
+            session_uri: session.session_uri,
+            path,
+            progress,
+        };
+        let result = generic_check_backoff_youtube(&self.client, &params, resume_attempt)
+            .await
+            .map_err(YoutubeError::backoff)?;
+        match result {
+            Ok(video) => Ok(video),
+            // the session is kept, the next upload probes it again
+            Err(e) => Err(YoutubeError::from_upload(e).await),
+        }
+    }
+
+    /// One attempt at finishing the session, see [`Self::resume_upload`].
+    async fn continue_session(&self, params: &ResumeParams<'_>) -> youtube::Result<Option<Video>> {
+        let size = std::fs::metadata(params.path)?.len();
+        let offset = match self.probe_session(&params.session_uri, size).await? {
+            SessionState::Complete(video) => return Ok(Some(video)),
+            SessionState::Incomplete { offset } => offset,
+            SessionState::Expired => {
+                warn!("upload session for {} expired, starting over", params.key);
+                params.store.remove(params.key);
+                return Ok(None);
+            }
+        };
+        info!(
+            "resuming upload session for {} at byte {} of {}",
+            params.key, offset, size
+        );
+        params.store.set_confirmed_offset(params.key, offset);
+
+        let mut file = std::fs::File::open(params.path)?;
+        file.seek(SeekFrom::Start(offset))?;
+        let mut reader: Box<dyn Read + Send + '_> = match params.progress {
+            Some(progress) => Box::new(ProgressReader::new(file, progress)?),
+            None => Box::new(file),
+        };
+        let (mut sender, body) = Body::channel();
+        let request = self
+            .session_request(&params.session_uri)
+            .await?
+            .header(
+                header::CONTENT_RANGE,
+                format!("bytes {}-{}/{}", offset, size.saturating_sub(1), size),
+            )
+            .header(header::CONTENT_LENGTH, size - offset)
+            .body(body)
+            .map_err(invalid_request)?;
+        let send_file = async move {
+            let mut chunk = vec![0; RESUME_CHUNK_SIZE];
+            loop {
+                let n = reader.read(&mut chunk)?;
+                // a closed body means the request is over, its response tells what went wrong
+                if n == 0 || sender.send_data(chunk[..n].to_vec().into()).await.is_err() {
+                    break;
+                }
+            }
+            Ok::<(), std::io::Error>(())
+        };
+        let (response, sent) = tokio::join!(self.client.client.request(request), send_file);
+        let response = response.map_err(youtube::Error::HttpError)?;
+        match response.status() {
+            StatusCode::OK | StatusCode::CREATED => {
+                sent?;
+                read_video(response).await.map(Some)
+            }
+            StatusCode::NOT_FOUND | StatusCode::GONE => {
+                warn!("upload session for {} expired, starting over", params.key);
+                params.store.remove(params.key);
+                Ok(None)
+            }
+            _ => Err(failure(response).await),
+        }
+    }
+
+    /// Asks the server how much of the upload it received with a
+    /// `Content-Range: bytes */N` request.
+    async fn probe_session(&self, session_uri: &str, size: u64) -> youtube::Result<SessionState> {
+        let request = self
+            .session_request(session_uri)
+            .await?
+            .header(header::CONTENT_RANGE, format!("bytes */{}", size))
+            .header(header::CONTENT_LENGTH, 0)
+            .body(Body::empty())
+            .map_err(invalid_request)?;
+        let response = self
+            .client
+            .client
+            .request(request)
+            .await
+            .map_err(youtube::Error::HttpError)?;
+        match response.status() {
+            StatusCode::OK | StatusCode::CREATED => {
+                read_video(response).await.map(SessionState::Complete)
+            }
+            StatusCode::PERMANENT_REDIRECT => Ok(SessionState::Incomplete {
+                offset: confirmed_offset(&response),
+            }),
+            StatusCode::NOT_FOUND | StatusCode::GONE => Ok(SessionState::Expired),
+            _ => Err(failure(response).await),
+        }
+    }
+
+    async fn session_request(
+        &self,
+        session_uri: &str,
+    ) -> youtube::Result<hyper::http::request::Builder> {
+        let token = self
+            .authenticator
+            .token(&self.scope_urls())
+            .await
+            .map_err(|e| youtube::Error::MissingToken(Box::new(e)))?;
+        let token = token.token().ok_or_else(|| {
+            youtube::Error::MissingToken("the authenticator returned no access token".into())
+        })?;
+        Ok(Request::builder()
+            .method(Method::PUT)
+            .uri(session_uri)
+            .header(header::AUTHORIZATION, format!("Bearer {}", token)))
+    }
+}
+
+/// A stored session uri that is not a valid uri.
+fn invalid_request(e: hyper::http::Error) -> youtube::Error {
+    youtube::Error::Io(std::io::Error::new(std::io::ErrorKind::InvalidInput, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+
+    use super::*;
+    use crate::{scopes, AuthMode};
+
+    /// Reads one request, returns the head in lowercase and the body.
+    async fn read_request(stream: &mut TcpStream) -> (String, Vec<u8>) {
+        let mut data = Vec::new();
+        let mut buf = [0; 1024];
+        let head_end = loop {
+            let n = stream.read(&mut buf).await.unwrap();
+            assert!(n > 0, "connection closed before the request was complete");
+            data.extend_from_slice(&buf[..n]);
+            if let Some(i) = data.windows(4).position(|w| w == b"\r\n\r\n") {
+                break i + 4;
+            }
+        };
+        let head = String::from_utf8_lossy(&data[..head_end]).to_lowercase();
+        let length = head
+            .lines()
+            .find_map(|line| line.strip_prefix("content-length:"))
+            .map(|length| length.trim().parse().unwrap())
+            .unwrap_or(0);
+        let mut body = data[head_end..].to_vec();
+        while body.len() < length {
+            let n = stream.read(&mut buf).await.unwrap();
+            assert!(n > 0, "connection closed before the body was complete");
+            body.extend_from_slice(&buf[..n]);
+        }
+        (head, body)
+    }
+
+    /// Answers the next request on a new connection, returns what was requested.
+    async fn respond(listener: &TcpListener, response: &str) -> (String, Vec<u8>) {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let request = read_request(&mut stream).await;
+        stream.write_all(response.as_bytes()).await.unwrap();
+        stream.shutdown().await.unwrap();
+        request
+    }
+
+    async fn client() -> YoutubeClient {
+        YoutubeClient::builder()
+            .auth_mode(AuthMode::AccessToken("test-token".to_string()))
+            .scopes(vec![scopes::YOUTUBE_UPLOAD])
+            .build()
+            .await
+            .unwrap()
+    }
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("google_youtube-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[tokio::test]
+    async fn resumes_at_confirmed_offset() {
+        let dir = test_dir("resume");
+        let video = dir.join("video.mp4");
+        std::fs::write(&video, b"0123456789").unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let session_uri = format!("http://{}/upload/session", listener.local_addr().unwrap());
+        let store = UploadSessionStore::new(dir.join("sessions.json"));
+        let key = UploadSessionStore::key_for(&video).unwrap();
+        store.set(
+            &key,
+            UploadSession {
+                session_uri,
+                confirmed_offset: 0,
+            },
+        );
+
+        let server = tokio::spawn(async move {
+            let probe = respond(
+                &listener,
+                "HTTP/1.1 308 Resume Incomplete\r\nRange: bytes=0-3\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+            )
+            .await;
+            let body = r#"{"id":"resumed"}"#;
+            let upload = respond(
+                &listener,
+                &format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                ),
+            )
+            .await;
+            (probe, upload)
+        });
+
+        let client = client().await;
+        let uploaded = client
+            .resume_upload(&store, &key, &video, None)
+            .await
+            .unwrap()
+            .expect("the session should be resumed");
+        assert_eq!(uploaded.id.as_deref(), Some("resumed"));
+
+        let ((probe, _), (upload, body)) = server.await.unwrap();
+        assert!(probe.starts_with("put /upload/session "));
+        assert!(probe.contains("content-range: bytes */10"));
+        assert!(probe.contains("authorization: bearer test-token"));
+        assert!(upload.contains("content-range: bytes 4-9/10"));
+        assert_eq!(body, b"456789");
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn retries_after_a_server_error() {
+        let dir = test_dir("retry");
+        let video = dir.join("video.mp4");
+        std::fs::write(&video, b"0123456789").unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let session_uri = format!("http://{}/upload/session", listener.local_addr().unwrap());
+        let store = UploadSessionStore::new(dir.join("sessions.json"));
+        let key = UploadSessionStore::key_for(&video).unwrap();
+        store.set(
+            &key,
+            UploadSession {
+                session_uri,
+                confirmed_offset: 0,
+            },
+        );
+
+        let server = tokio::spawn(async move {
+            respond(
+                &listener,
+                "HTTP/1.1 308 Resume Incomplete\r\nRange: bytes=0-3\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+            )
+            .await;
+            let error = r#"{"error":{"code":503,"message":"Backend Error","errors":[{"reason":"backendError"}]}}"#;
+            respond(
+                &listener,
+                &format!(
+                    "HTTP/1.1 503 Service Unavailable\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    error.len(),
+                    error
+                ),
+            )
+            .await;
+            // the server kept part of the failed request
+            respond(
+                &listener,
+                "HTTP/1.1 308 Resume Incomplete\r\nRange: bytes=0-5\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+            )
+            .await;
+            let body = r#"{"id":"retried"}"#;
+            respond(
+                &listener,
+                &format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                ),
+            )
+            .await
+        });
+
+        let client = client().await;
+        let uploaded = client
+            .resume_upload(&store, &key, &video, None)
+            .await
+            .unwrap()
+            .expect("the session should be resumed after the retry");
+        assert_eq!(uploaded.id.as_deref(), Some("retried"));
+
+        let (upload, body) = server.await.unwrap();
+        assert!(upload.contains("content-range: bytes 6-9/10"));
+        assert_eq!(body, b"6789");
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn expired_session_starts_over() {
+        let dir = test_dir("expired");
+        let video = dir.join("video.mp4");
+        std::fs::write(&video, b"0123456789").unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let session_uri = format!("http://{}/upload/session", listener.local_addr().unwrap());
+        let store = UploadSessionStore::new(dir.join("sessions.json"));
+        let key = UploadSessionStore::key_for(&video).unwrap();
+        store.set(
+            &key,
+            UploadSession {
+                session_uri,
+                confirmed_offset: 4,
+            },
+        );
+
+        let server = tokio::spawn(async move {
+            respond(
+                &listener,
+                "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+            )
+            .await
+        });
+
+        let client = client().await;
+        let uploaded = client
+            .resume_upload(&store, &key, &video, None)
+            .await
+            .unwrap();
+        assert!(uploaded.is_none());
+        assert!(store.get(&key).is_none());
+        server.await.unwrap();
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn key_changes_with_the_file() {
+        let dir = test_dir("key");
+        let video = dir.join("video.mp4");
+        std::fs::write(&video, b"0123456789").unwrap();
+        let key = UploadSessionStore::key_for(&video).unwrap();
+        assert_eq!(key, UploadSessionStore::key_for(&video).unwrap());
+        std::fs::write(&video, b"01234567890").unwrap();
+        assert_ne!(key, UploadSessionStore::key_for(&video).unwrap());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,272 @@
+//! Client-side checks of the limits youtube puts on video metadata.
+//!
+//! Youtube only rejects invalid metadata after the whole file was uploaded,
+//! so these run before any bytes go over the wire.
+
+use google_youtube3::api::Video;
+
+use crate::prelude::*;
+
+pub const MAX_TITLE_CHARS: usize = 100;
+pub const MAX_DESCRIPTION_BYTES: usize = 5000;
+pub const MAX_TAGS_CHARS: usize = 500;
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ValidationError {
+    #[error("the title is empty")]
+    EmptyTitle,
+    #[error("the title is {len} characters long, the maximum is {max}")]
+    TitleTooLong { len: usize, max: usize },
+    #[error("the title contains '<' or '>'")]
+    TitleInvalidCharacters,
+    #[error("the description is {len} bytes long, the maximum is {max}")]
+    DescriptionTooLong { len: usize, max: usize },
+    #[error("the description contains '<' or '>'")]
+    DescriptionInvalidCharacters,
+    #[error("the tags are {len} characters long in total, the maximum is {max}")]
+    TagsTooLong { len: usize, max: usize },
+}
+
+/// What to do with video metadata that exceeds the youtube limits.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum ValidationMode {
+    /// Refuse to upload invalid metadata.
+    #[default]
+    Strict,
+    /// Truncate and strip the metadata until it fits, only refuse what can't be fixed (an empty title).
+    Sanitize,
+    /// Send the metadata as is and let youtube decide.
+    Disabled,
+}
+
+fn has_invalid_characters(s: &str) -> bool {
+    s.contains(['<', '>'])
+}
+
+fn strip_invalid_characters(s: &str) -> String {
+    s.replace(['<', '>'], "")
+}
+
+/// The length youtube counts for a list of tags: tags are separated by
+/// commas and tags containing spaces are quoted.
+pub fn tags_length(tags: &[String]) -> usize {
+    let separators = tags.len().saturating_sub(1);
+    tags.iter()
+        .map(|tag| {
+            let len = tag.chars().count();
+            if tag.contains(' ') {
+                len + 2
+            } else {
+                len
+            }
+        })
+        .sum::<usize>()
+        + separators
+}
+
+fn truncate_bytes(s: &mut String, max: usize) {
+    if s.len() <= max {
+        return;
+    }
+    let mut end = max;
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    s.truncate(end);
+}
+
+/// Checks the snippet of the video against the youtube limits and returns every violation.
+pub fn validate_video(video: &Video) -> Result<(), Vec<ValidationError>> {
+    let mut errors = vec![];
+    let snippet = video.snippet.as_ref();
+
+    let title = snippet.and_then(|s| s.title.as_deref()).unwrap_or_default();
+    let title_len = title.chars().count();
+    if title.trim().is_empty() {
+        errors.push(ValidationError::EmptyTitle);
+    }
+    if title_len > MAX_TITLE_CHARS {
+        errors.push(ValidationError::TitleTooLong {
+            len: title_len,
+            max: MAX_TITLE_CHARS,
+        });
+    }
+    if has_invalid_characters(title) {
+        errors.push(ValidationError::TitleInvalidCharacters);
+    }
+
+    if let Some(description) = snippet.and_then(|s| s.description.as_deref()) {
+        if description.len() > MAX_DESCRIPTION_BYTES {
+            errors.push(ValidationError::DescriptionTooLong {
+                len: description.len(),
+                max: MAX_DESCRIPTION_BYTES,
+            });
+        }
+        if has_invalid_characters(description) {
+            errors.push(ValidationError::DescriptionInvalidCharacters);
+        }
+    }
+
+    if let Some(tags) = snippet.and_then(|s| s.tags.as_deref()) {
+        let len = tags_length(tags);
+        if len > MAX_TAGS_CHARS {
+            errors.push(ValidationError::TagsTooLong {
+                len,
+                max: MAX_TAGS_CHARS,
+            });
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+/// Strips invalid characters and truncates the title, description and tags to the youtube limits.
+pub fn sanitize_video(video: &mut Video) {
+    let snippet = match video.snippet.as_mut() {
+        Some(snippet) => snippet,
+        None => return,
+    };
+
+    if let Some(title) = snippet.title.as_mut() {
+        let mut sanitized = strip_invalid_characters(title);
+        if let Some((end, _)) = sanitized.char_indices().nth(MAX_TITLE_CHARS) {
+            sanitized.truncate(end);
+        }
+        if sanitized != *title {
+            warn!("sanitized title: '{}' => '{}'", title, sanitized);
+            *title = sanitized;
+        }
+    }
+
+    if let Some(description) = snippet.description.as_mut() {
+        let mut sanitized = strip_invalid_characters(description);
+        truncate_bytes(&mut sanitized, MAX_DESCRIPTION_BYTES);
+        if sanitized != *description {
+            warn!("sanitized description");
+            *description = sanitized;
+        }
+    }
+
+    if let Some(tags) = snippet.tags.as_mut() {
+        while tags_length(tags) > MAX_TAGS_CHARS {
+            let removed = tags.pop();
+            warn!("dropped tag to fit the tag limit: {:?}", removed);
+        }
+    }
+}
+
+/// Applies the validation mode to a video that is about to be sent.
+pub(crate) fn check_video(
+    mode: ValidationMode,
+    video: &mut Video,
+) -> Result<(), Vec<ValidationError>> {
+    match mode {
+        ValidationMode::Strict => validate_video(video),
+        ValidationMode::Sanitize => {
+            sanitize_video(video);
+            validate_video(video)
+        }
+        ValidationMode::Disabled => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use google_youtube3::api::VideoSnippet;
+
+    use super::*;
+
+    fn video_with(title: &str, description: &str, tags: &[&str]) -> Video {
+        Video {
+            snippet: Some(VideoSnippet {
+                title: Some(title.to_string()),
+                description: Some(description.to_string()),
+                tags: Some(tags.iter().map(|t| t.to_string()).collect()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn tags(tags: &[&str]) -> Vec<String> {
+        tags.iter().map(|t| t.to_string()).collect()
+    }
+
+    #[test]
+    fn tags_length_counts_separators_and_quotes() {
+        assert_eq!(tags_length(&[]), 0);
+        assert_eq!(tags_length(&tags(&["rust"])), 4);
+        // rust,youtube
+        assert_eq!(tags_length(&tags(&["rust", "youtube"])), 12);
+        // rust,"live stream"
+        assert_eq!(tags_length(&tags(&["rust", "live stream"])), 18);
+        // characters, not bytes
+        assert_eq!(tags_length(&tags(&["über"])), 4);
+    }
+
+    #[test]
+    fn validate_reports_every_violation() {
+        let title = "a".repeat(MAX_TITLE_CHARS + 1);
+        let errors = validate_video(&video_with(&title, "<b>", &[])).unwrap_err();
+        assert_eq!(
+            errors,
+            vec![
+                ValidationError::TitleTooLong {
+                    len: MAX_TITLE_CHARS + 1,
+                    max: MAX_TITLE_CHARS
+                },
+                ValidationError::DescriptionInvalidCharacters,
+            ]
+        );
+        assert_eq!(
+            validate_video(&video_with(" ", "", &[])).unwrap_err(),
+            vec![ValidationError::EmptyTitle]
+        );
+        assert!(validate_video(&video_with("title", "description", &["tag"])).is_ok());
+    }
+
+    #[test]
+    fn sanitize_truncates_multibyte_text_on_char_boundaries() {
+        let title = "ä".repeat(MAX_TITLE_CHARS + 10);
+        // 'ä' is two bytes, after the leading 'x' the limit falls in the middle of one
+        let description = format!("x{}", "ä".repeat(MAX_DESCRIPTION_BYTES));
+        let mut video = video_with(&title, &description, &[]);
+        sanitize_video(&mut video);
+        assert!(validate_video(&video).is_ok());
+        let snippet = video.snippet.unwrap();
+        assert_eq!(snippet.title.unwrap().chars().count(), MAX_TITLE_CHARS);
+        assert_eq!(
+            snippet.description.unwrap().len(),
+            MAX_DESCRIPTION_BYTES - 1
+        );
+    }
+
+    #[test]
+    fn sanitize_strips_angle_brackets() {
+        let mut video = video_with("<title>", "a > b", &[]);
+        sanitize_video(&mut video);
+        let snippet = video.snippet.unwrap();
+        assert_eq!(snippet.title.as_deref(), Some("title"));
+        assert_eq!(snippet.description.as_deref(), Some("a  b"));
+    }
+
+    #[test]
+    fn sanitize_drops_tags_from_the_end_until_they_fit() {
+        let long_tag = "a".repeat(200);
+        let mut video = video_with("title", "", &[&long_tag, &long_tag, "live stream"]);
+        sanitize_video(&mut video);
+        // 200 + 1 + 200 + 1 + 13 fits, the quotes of the last tag are counted as well
+        assert_eq!(video.snippet.unwrap().tags.unwrap().len(), 3);
+
+        let mut video = video_with("title", "", &[&long_tag, &long_tag, &long_tag]);
+        sanitize_video(&mut video);
+        assert_eq!(
+            video.snippet.unwrap().tags.unwrap(),
+            vec![long_tag.clone(), long_tag]
+        );
+    }
+}
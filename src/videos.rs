@@ -0,0 +1,171 @@
+use chrono::{DateTime, Utc};
+use exponential_backoff::youtube::generic_check_backoff_youtube;
+use google_youtube3::{
+    api::{Video, VideoListResponse},
+    hyper::{client::HttpConnector, Body, Response},
+    hyper_rustls::HttpsConnector,
+    YouTube,
+};
+#[cfg(feature = "tracing")]
+use tracing::instrument;
+
+use crate::channel::{ContentOwner, OwnedRequest};
+use crate::prelude::*;
+use crate::quota::QuotaOperation;
+use crate::scopes::Scope;
+use crate::validation::check_video;
+use crate::{PrivacyStatus, VideoPatch, YoutubeClient, YoutubeError};
+
+impl YoutubeClient {
+    /// Fetches a single video with the given parts.
+    #[cfg_attr(feature = "tracing", instrument)]
+    pub async fn get_video(&self, video_id: &str, parts: &[&str]) -> Result<Video, YoutubeError> {
+        struct VideoParams {
+            part: Vec<String>,
+            id: String,
+            content_owner: Option<ContentOwner>,
+        }
+        async fn get_video(
+            client: &YouTube<HttpsConnector<HttpConnector>>,
+            params: &VideoParams,
+        ) -> google_youtube3::Result<(Response<Body>, VideoListResponse)> {
+            let mut call = client.videos().list(&params.part).add_id(&params.id);
+            if let Some(owner) = &params.content_owner {
+                call = call.on_behalf_of_content_owner(&owner.owner);
+            }
+            call.doit().await
+        }
+        let params = VideoParams {
+            part: parts.iter().map(|p| p.to_string()).collect(),
+            id: video_id.to_string(),
+            content_owner: self.content_owner.clone(),
+        };
+        self.prepare_call(Scope::Readonly, QuotaOperation::List)
+            .await?;
+        let (_res, videos) = YoutubeError::api(
+            generic_check_backoff_youtube(&self.client, &params, get_video).await,
+        )
+        .await?;
+        videos
+            .items
+            .and_then(|items| items.into_iter().next())
+            .ok_or_else(|| YoutubeError::VideoNotFound(video_id.to_string()))
+    }
+
+    /// Sends the video as is, every part that is set replaces the current one.
+    async fn update_video(&self, video: &Video) -> Result<Video, YoutubeError> {
+        async fn update_video(
+            client: &YouTube<HttpsConnector<HttpConnector>>,
+            request: &OwnedRequest<Video>,
+        ) -> google_youtube3::Result<(Response<Body>, Video)> {
+            let mut call = client.videos().update(request.body.clone());
+            if let Some(owner) = &request.content_owner {
+                call = call.on_behalf_of_content_owner(&owner.owner);
+            }
+            call.doit().await
+        }
+        let request = self.owned_request(video.clone());
+        self.prepare_call(Scope::Full, QuotaOperation::Update)
+            .await?;
+        let (res, video) = YoutubeError::api(
+            generic_check_backoff_youtube(&self.client, &request, update_video).await,
+        )
+        .await?;
+        if res.status().is_success() {
+            Ok(video)
+        } else {
+            Err(YoutubeError::status(res).await)
+        }
+    }
+
+    /// Changes only the fields that are set in the patch.
+    ///
+    /// The api replaces the snippet and status as a whole and wipes everything
+    /// that is omitted, so the current values are fetched and merged first.
+    #[cfg_attr(feature = "tracing", instrument)]
+    pub async fn update_video_metadata(
+        &self,
+        video_id: &str,
+        patch: &VideoPatch,
+    ) -> Result<Video, YoutubeError> {
+        if let Some(privacy) = &patch.privacy_status {
+            privacy.validate()?;
+        }
+        let current = self
+            .get_video(video_id, &["snippet", "status", "recordingDetails"])
+            .await?;
+        let category_id = match &patch.category {
+            Some(category) => Some(
+                self.resolve_category(category, patch.region_code.as_deref())
+                    .await?,
+            ),
+            None => None,
+        };
+
+        let mut video = Video {
+            id: Some(video_id.to_string()),
+            ..Default::default()
+        };
+        if patch.changes_snippet() {
+            let mut snippet = current.snippet.clone().unwrap_or_default();
+            patch.apply_to_snippet(&mut snippet, category_id);
+            video.snippet = Some(snippet);
+        }
+        if patch.changes_status() {
+            let mut status = current.status.clone().unwrap_or_default();
+            patch.apply_to_status(&mut status);
+            video.status = Some(status);
+        }
+        if let Some(recording_date) = patch.recording_date {
+            let mut recording_details = current.recording_details.clone().unwrap_or_default();
+            recording_details.recording_date = Some(recording_date);
+            video.recording_details = Some(recording_details);
+        }
+        if video.snippet.is_none() && video.status.is_none() && video.recording_details.is_none() {
+            debug!("nothing to update for video {}", video_id);
+            return Ok(current);
+        }
+
+        if video.snippet.is_some() {
+            check_video(self.validation_mode, &mut video).map_err(YoutubeError::Validation)?;
+        }
+        self.update_video(&video).await
+    }
+
+    /// Makes the video private and lets youtube publish it at the given time.
+    #[cfg_attr(feature = "tracing", instrument)]
+    pub async fn reschedule_video(
+        &self,
+        video_id: &str,
+        when: DateTime<Utc>,
+    ) -> Result<Video, YoutubeError> {
+        let patch = VideoPatch::new().privacy(PrivacyStatus::Scheduled(when));
+        self.update_video_metadata(video_id, &patch).await
+    }
+
+    #[cfg_attr(feature = "tracing", instrument)]
+    pub async fn delete_video(&self, video_id: &str) -> Result<(), YoutubeError> {
+        async fn delete_video(
+            client: &YouTube<HttpsConnector<HttpConnector>>,
+            request: &OwnedRequest<String>,
+        ) -> google_youtube3::Result<Response<Body>> {
+            let mut call = client.videos().delete(&request.body);
+            if let Some(owner) = &request.content_owner {
+                call = call.on_behalf_of_content_owner(&owner.owner);
+            }
+            call.doit().await
+        }
+        let params = self.owned_request(video_id.to_string());
+        self.prepare_call(Scope::Full, QuotaOperation::Delete)
+            .await?;
+        let res = YoutubeError::api(
+            generic_check_backoff_youtube(&self.client, &params, delete_video).await,
+        )
+        .await?;
+        if res.status().is_success() {
+            Ok(())
+        } else {
+            Err(YoutubeError::status(res).await)
+        }
+    }
+}